@@ -0,0 +1,79 @@
+//! Compares sampling animations with binary searches against sampling with [AnimationCursor]s.
+//! Run with `cargo bench --bench sampling`
+use geng::prelude::*;
+use geng_gltf::*;
+
+const NODES: usize = 60;
+const KEYFRAMES: usize = 1800;
+const CHARACTERS: usize = 300;
+const FRAMES: usize = 600;
+
+fn mocap_animation() -> Animation {
+    let inputs = (0..KEYFRAMES).map(|i| i as f32 / 30.0).collect_vec();
+    let channels = (0..NODES)
+        .flat_map(|node| {
+            let rotation = Channel {
+                target: node,
+                interpolation: Interpolation::Linear,
+                inputs: inputs.clone(),
+                outputs: Outputs::Rotation(
+                    inputs
+                        .iter()
+                        .map(|&t| {
+                            let angle = (t + node as f32).sin() * 0.5;
+                            vec4(0.0, angle.sin(), 0.0, angle.cos())
+                        })
+                        .collect(),
+                ),
+            };
+            let translation = Channel {
+                target: node,
+                interpolation: Interpolation::Linear,
+                inputs: inputs.clone(),
+                outputs: Outputs::Translation(
+                    inputs
+                        .iter()
+                        .map(|&t| vec3(t.cos(), 1.0, t.sin()))
+                        .collect(),
+                ),
+            };
+            [rotation, translation]
+        })
+        .collect();
+    Animation {
+        name: Some("Mocap".to_owned()),
+        ..Animation::new(channels)
+    }
+}
+
+fn run(name: &str, mut sample: impl FnMut(usize, f32, &mut Pose)) {
+    let mut pose = Pose::new(vec![Transform::identity(); NODES], Vec::new());
+    let start = std::time::Instant::now();
+    for frame in 0..FRAMES {
+        for character in 0..CHARACTERS {
+            // Characters are out of phase, so they don't share keyframes
+            let time = (frame as f32 / 60.0 + character as f32 * 0.37) % (KEYFRAMES as f32 / 30.0);
+            sample(character, time, &mut pose);
+        }
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{name}: {:?} total, {:?} per frame ({} characters)",
+        elapsed,
+        elapsed / FRAMES as u32,
+        CHARACTERS
+    );
+}
+
+fn main() {
+    let animation = mocap_animation();
+
+    run("binary search", |_, time, pose| {
+        animation.sample(time, pose)
+    });
+
+    let mut cursors = vec![AnimationCursor::new(); CHARACTERS];
+    run("cursor", |character, time, pose| {
+        animation.sample_cached(time, pose, &mut cursors[character])
+    });
+}
@@ -64,7 +64,8 @@ fn main() {
                 rot_v: Angle::from_radians(f32::PI / 3.0),
             };
 
-            let start = std::time::Instant::now();
+            let mut player = geng_gltf::AnimationPlayer::new(&model, "ArmatureAction").unwrap();
+            let mut last_frame = std::time::Instant::now();
 
             let mut events = geng.window().events();
             while let Some(event) = events.next().await {
@@ -83,15 +84,16 @@ fn main() {
                     }
 
                     geng::Event::Draw => {
+                        let now = std::time::Instant::now();
+                        player.update((now - last_frame).as_secs_f32());
+                        last_frame = now;
+
                         geng.window().with_framebuffer(|framebuffer| {
                             let framebuffer_size = framebuffer.size().map(|x| x as f32);
                             ugli::clear(framebuffer, Some(Rgba::BLACK), Some(1.0), None);
 
                             model.reset_transforms();
-                            model.apply_animation(
-                                "ArmatureAction",
-                                start.elapsed().as_secs_f32() % 7.5,
-                            );
+                            player.apply(&mut model).unwrap();
 
                             model.draw(
                                 framebuffer,
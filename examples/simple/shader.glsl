@@ -49,6 +49,6 @@ void main() {
     float diff = max(dot(v_normal, light_dir), 0.0);
     vec3 diffuse = diff * light_color;
 
-    gl_FragColor = vec4(ambient + diffuse, 1.0) * sample_material_texture(v_uv) * v_color;
+    gl_FragColor = vec4(ambient + diffuse, 1.0) * sample_material_texture(v_uv) * v_color + vec4(material_emissive(), 0.0);
 }
 #endif
\ No newline at end of file
@@ -1,30 +1,128 @@
 use super::*;
 
+#[derive(Debug, Clone)]
 pub enum Outputs {
-    Translation(Vec<[f32; 3]>),
-    Rotation(Vec<[f32; 4]>),
-    Scale(Vec<[f32; 3]>),
+    Translation(Vec<vec3<f32>>),
+    Rotation(Vec<vec4<f32>>),
+    /// Rotations, with every component stored as a normalized i16 (see [Animation::compress])
+    QuantizedRotation(Vec<[i16; 4]>),
+    Scale(Vec<vec3<f32>>),
+    /// Morph target weights of the target node, one list of outputs per morph target.
+    /// Kept even if the node has no mesh (e.g. in a skeleton-only file), so retargeting can still map it
+    Weights(Vec<Vec<f32>>),
+    /// Property of a material, camera or light (`KHR_animation_pointer`), one list of outputs per component.
+    /// Such channels don't animate nodes, so they are not part of a [Pose]. They are applied by [Model::apply_animation],
+    /// [Model::blend_animations] and [Model::apply_animation_properties]
+    Property {
+        pointer: AnimationPointer,
+        outputs: Vec<Vec<f32>>,
+    },
 }
 
+/// How values are interpolated between keyframes of a [Channel]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Value of the previous keyframe is held until the next keyframe
+    Step,
+    /// Values are linearly interpolated (and rotations are slerped)
+    Linear,
+    /// Cubic hermite spline. Outputs of such channels are stored as
+    /// (in-tangent, value, out-tangent) triples, like in the glTF buffer
+    CubicSpline,
+}
+
+#[derive(Debug, Clone)]
 pub struct Channel {
-    target: usize,
-    inputs: Vec<f32>,
-    outputs: Outputs,
+    /// Index of the animated node
+    pub target: usize,
+    pub interpolation: Interpolation,
+    /// Keyframe times
+    pub inputs: Vec<f32>,
+    pub outputs: Outputs,
+}
+
+/// Pose, that an additive animation is relative to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdditiveReference {
+    /// First frame of the animation itself
+    FirstFrame,
+    /// First frame of another animation
+    Animation(Id),
+}
+
+/// Named marker on the animation timeline, e.g. a footstep or a hit frame
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AnimationEvent {
+    pub name: String,
+    pub time: f32,
 }
 
+/// Application specific data of an animation, that we understand
+#[derive(Debug, Clone, Default, Deserialize)]
+struct AnimationExtras {
+    #[serde(default)]
+    events: Vec<AnimationEvent>,
+    #[serde(default)]
+    clips: Vec<SubClip>,
+}
+
+#[derive(Debug, Clone)]
 pub struct Animation {
+    pub name: Option<String>,
     pub channels: Vec<Channel>,
+    /// Events, sorted by time. Loaded from animation extras in the form of
+    /// `{ "events": [{ "name": "footstep", "time": 0.5 }] }`
+    pub events: Vec<AnimationEvent>,
+    /// Sub-clips, loaded from animation extras in the form of
+    /// `{ "clips": [{ "name": "walk", "start": 2.0, "end": 3.0 }] }`.
+    /// [Model] adds them as separate animations when loading
+    pub clips: Vec<SubClip>,
+    /// Reference pose, if the animation is additive (see [Model::make_additive])
+    pub additive: Option<Pose>,
 }
 
 impl Animation {
+    /// Animation with the given channels
+    pub fn new(channels: Vec<Channel>) -> Self {
+        Self {
+            name: None,
+            channels,
+            events: Vec::new(),
+            clips: Vec::new(),
+            additive: None,
+        }
+    }
+
+    /// Loads the animation. `KHR_animation_pointer` channels can't be parsed by the gltf crate,
+    /// so they are only loaded by [Model::load] and [Model::from_slice]
     pub fn load(
         animation: gltf::Animation<'_>,
         buffers: &[gltf::buffer::Data],
+    ) -> Result<Self, MeshLoadError> {
+        Self::load_with_pointers(animation, buffers, &[])
+    }
+
+    /// Loads the animation with it's `KHR_animation_pointer` channels, given as (sampler index, pointer)
+    pub(crate) fn load_with_pointers(
+        animation: gltf::Animation<'_>,
+        buffers: &[gltf::buffer::Data],
+        pointers: &[(usize, String)],
     ) -> Result<Self, MeshLoadError> {
         let mut channels = Vec::new();
+        for (sampler, pointer) in pointers {
+            channels.extend(Channel::load_pointer(
+                &animation, *sampler, pointer, buffers,
+            )?);
+        }
         for channel in animation.channels() {
+            let interpolation = match channel.sampler().interpolation() {
+                gltf::animation::Interpolation::Step => Interpolation::Step,
+                gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+            };
+
             let reader = channel.reader(|buffer| buffers.get(buffer.index()).map(|x| &**x));
-            let inputs = reader
+            let inputs: Vec<f32> = reader
                 .read_inputs()
                 .ok_or(MeshLoadError::MissingAnimationInputs)?
                 .collect();
@@ -35,63 +133,408 @@ impl Animation {
             use gltf::animation::util::ReadOutputs;
             let outputs = match outputs {
                 ReadOutputs::Translations(translations) => {
-                    Outputs::Translation(translations.collect())
+                    Outputs::Translation(translations.map(|[x, y, z]| vec3(x, y, z)).collect())
+                }
+                ReadOutputs::Rotations(rotations) => Outputs::Rotation(
+                    rotations
+                        .into_f32()
+                        .map(|[x, y, z, w]| vec4(x, y, z, w))
+                        .collect(),
+                ),
+                ReadOutputs::Scales(scales) => {
+                    Outputs::Scale(scales.map(|[x, y, z]| vec3(x, y, z)).collect())
                 }
-                ReadOutputs::Rotations(rotations) => {
-                    Outputs::Rotation(rotations.into_f32().collect())
+                ReadOutputs::MorphTargetWeights(weights) => {
+                    let outputs_count = match interpolation {
+                        Interpolation::CubicSpline => inputs.len() * 3,
+                        _ => inputs.len(),
+                    };
+                    Outputs::Weights(deinterleave_weights(
+                        &weights.into_f32().collect_vec(),
+                        outputs_count,
+                    ))
                 }
-                ReadOutputs::Scales(scales) => Outputs::Scale(scales.collect()),
-                ReadOutputs::MorphTargetWeights(_) => todo!("Morph targets"),
             };
 
             channels.push(Channel {
                 target: channel.target().node().index(),
+                interpolation,
                 inputs,
                 outputs,
             });
         }
-        Ok(Self { channels })
+        let extras = match animation.extras() {
+            Some(extras) => serde_json::from_str(extras.get()).unwrap_or_else(|err| {
+                log::warn!("Failed to parse animation extras: {}", err);
+                AnimationExtras::default()
+            }),
+            None => AnimationExtras::default(),
+        };
+        let mut events = extras.events;
+        events.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        Ok(Self {
+            name: animation.name().map(str::to_owned),
+            channels,
+            events,
+            clips: extras.clips,
+            additive: None,
+        })
+    }
+
+    /// Writes animated values at `time` into node transforms and morph target weights.
+    /// Animated components replace the old values, all other components are kept
+    pub(crate) fn sample_into(
+        &self,
+        time: f32,
+        transforms: &mut [Transform],
+        weights: &mut [Vec<f32>],
+    ) {
+        self.sample_keyframes_into(|_, channel| channel.keyframes(time), transforms, weights);
+    }
+
+    /// Like [Animation::sample_into], but keyframes of every channel are found by `find_keyframes(index, channel)`
+    pub(crate) fn sample_keyframes_into(
+        &self,
+        mut find_keyframes: impl FnMut(usize, &Channel) -> (usize, usize, f32),
+        transforms: &mut [Transform],
+        weights: &mut [Vec<f32>],
+    ) {
+        for (index, channel) in self.channels.iter().enumerate() {
+            let keyframes = find_keyframes(index, channel);
+            match &channel.outputs {
+                Outputs::Weights(outputs) => {
+                    for (weight, outputs) in std::iter::zip(&mut weights[channel.target], outputs) {
+                        *weight =
+                            channel.sample_keyframes(keyframes, |index| outputs[index], lerp_f32);
+                    }
+                }
+                Outputs::Property { .. } => {}
+                _ => channel.sample_transform_at(keyframes, &mut transforms[channel.target]),
+            }
+        }
+    }
+
+    /// Samples only channels, that animate the transform of the given node
+    pub fn sample_node(&self, time: f32, node: usize, transform: &mut Transform) {
+        for channel in &self.channels {
+            if channel.target == node {
+                channel.sample_transform(time, transform);
+            }
+        }
+    }
+
+    /// Time of the last keyframe
+    pub fn duration(&self) -> f32 {
+        self.channels
+            .iter()
+            .filter_map(|channel| channel.inputs.last().copied())
+            .fold(0.0, f32::max)
     }
 }
 
+impl Channel {
+    /// Finds keyframes surrounding `time`.
+    /// Returns indices of the previous and the next keyframe and the interpolation factor between them
+    pub(crate) fn keyframes(&self, time: f32) -> (usize, usize, f32) {
+        match self.inputs.binary_search_by(|t| t.total_cmp(&time)) {
+            Ok(index) => (index, index, 0.0),
+            Err(0) => (0, 0, 0.0),
+            Err(index) if index == self.inputs.len() => (index - 1, index - 1, 0.0),
+            Err(index) => {
+                let (prev, next) = (self.inputs[index - 1], self.inputs[index]);
+                (index - 1, index, (time - prev) / (next - prev))
+            }
+        }
+    }
+
+    /// Writes animated value at `time` into the transform. Does nothing for morph target weights
+    fn sample_transform(&self, time: f32, transform: &mut Transform) {
+        self.sample_transform_at(self.keyframes(time), transform);
+    }
+
+    /// Like [Channel::sample_transform], but with already found keyframes (see [Channel::keyframes])
+    fn sample_transform_at(&self, keyframes: (usize, usize, f32), transform: &mut Transform) {
+        match &self.outputs {
+            Outputs::Translation(translations) => {
+                transform.translation =
+                    self.sample_keyframes(keyframes, |index| translations[index], lerp);
+            }
+            Outputs::Rotation(rotations) => {
+                // Cubic spline output isn't normalized
+                transform.rotation = normalize_quat(self.sample_keyframes(
+                    keyframes,
+                    |index| rotations[index],
+                    slerp,
+                ));
+            }
+            Outputs::QuantizedRotation(rotations) => {
+                let rotation = |index: usize| dequantize_rotation(rotations[index]);
+                transform.rotation =
+                    normalize_quat(self.sample_keyframes(keyframes, rotation, slerp));
+            }
+            Outputs::Scale(scales) => {
+                transform.scale = self.sample_keyframes(keyframes, |index| scales[index], lerp);
+            }
+            Outputs::Weights(_) | Outputs::Property { .. } => {}
+        }
+    }
+
+    pub(crate) fn sample<T>(&self, time: f32, outputs: &[T], lerp: impl Fn(T, T, f32) -> T) -> T
+    where
+        T: Copy + std::ops::Add<Output = T> + std::ops::Mul<f32, Output = T>,
+    {
+        self.sample_keyframes(self.keyframes(time), |index| outputs[index], lerp)
+    }
+
+    /// Interpolates outputs between the keyframes. Outputs are read through the `output` function,
+    /// so that they can be decompressed on the fly
+    fn sample_keyframes<T>(
+        &self,
+        (prev, next, t): (usize, usize, f32),
+        output: impl Fn(usize) -> T,
+        lerp: impl Fn(T, T, f32) -> T,
+    ) -> T
+    where
+        T: Copy + std::ops::Add<Output = T> + std::ops::Mul<f32, Output = T>,
+    {
+        match self.interpolation {
+            Interpolation::Step => output(prev),
+            Interpolation::Linear => lerp(output(prev), output(next), t),
+            Interpolation::CubicSpline => {
+                // https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html#interpolation-cubic
+                let delta_time = self.inputs[next] - self.inputs[prev];
+                let t2 = t * t;
+                let t3 = t2 * t;
+                let value = |index: usize| output(index * 3 + 1);
+                let in_tangent = |index: usize| output(index * 3);
+                let out_tangent = |index: usize| output(index * 3 + 2);
+
+                value(prev) * (2.0 * t3 - 3.0 * t2 + 1.0)
+                    + out_tangent(prev) * (delta_time * (t3 - 2.0 * t2 + t))
+                    + value(next) * (-2.0 * t3 + 3.0 * t2)
+                    + in_tangent(next) * (delta_time * (t3 - t2))
+            }
+        }
+    }
+}
+
+/// Weights are stored interleaved, one value per morph target for each output.
+/// Splits them into one list of outputs per morph target
+fn deinterleave_weights(weights: &[f32], outputs_count: usize) -> Vec<Vec<f32>> {
+    let targets = weights.len() / outputs_count.max(1);
+    (0..targets)
+        .map(|target| {
+            weights
+                .iter()
+                .skip(target)
+                .step_by(targets)
+                .copied()
+                .collect()
+        })
+        .collect()
+}
+
+pub(crate) fn lerp_f32(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+pub(crate) fn lerp(a: vec3<f32>, b: vec3<f32>, t: f32) -> vec3<f32> {
+    a + (b - a) * t
+}
+
 impl Model {
+    /// Resets all nodes to their rest pose (as they are in the glTF file),
+    /// and animated material, camera and light properties to their values from before animating
     pub fn reset_transforms(&mut self) {
-        self.transforms.fill(mat4::identity());
+        self.transforms.clone_from(&self.rest_transforms);
+        self.weights.clone_from(&self.default_weights);
+        self.reset_properties();
     }
 
-    pub fn apply_animation(&mut self, name: &str, time: f32) {
-        let Some(animation) = self.animations.get(name) else {
-            log::error!("Animation {:?} not found", name);
-            return;
+    /// Finds index of an animation in [Model::animations]
+    pub fn animation_index(&self, id: impl Into<Id>) -> Result<usize, AnimationError> {
+        let id = id.into();
+        let index = match &id {
+            Id::Index(index) => Some(*index).filter(|&index| index < self.animations.len()),
+            Id::Name(name) => self.animation_names.get(name).copied(),
         };
+        index.ok_or(AnimationError::NotFound(id))
+    }
 
-        for channel in &animation.channels {
-            let index = match channel.inputs.binary_search_by(|t| t.total_cmp(&time)) {
-                Ok(index) => index,
-                Err(index) => index.saturating_sub(1),
-            };
-            let transform = mat4::new(
-                match &channel.outputs {
-                    Outputs::Translation(translations) => gltf::scene::Transform::Decomposed {
-                        translation: translations[index],
-                        rotation: [0.0, 0.0, 0.0, 1.0],
-                        scale: [1.0, 1.0, 1.0],
-                    },
-                    Outputs::Rotation(rotations) => gltf::scene::Transform::Decomposed {
-                        translation: [0.0, 0.0, 0.0],
-                        rotation: rotations[index],
-                        scale: [1.0, 1.0, 1.0],
-                    },
-                    Outputs::Scale(scales) => gltf::scene::Transform::Decomposed {
-                        translation: [0.0, 0.0, 0.0],
-                        rotation: [0.0, 0.0, 0.0, 1.0],
-                        scale: scales[index],
-                    },
-                }
-                .matrix(),
-            );
+    pub fn animation(&self, id: impl Into<Id>) -> Result<&Animation, AnimationError> {
+        let index = self.animation_index(id)?;
+        Ok(&self.animations[index])
+    }
+
+    pub fn animation_mut(&mut self, id: impl Into<Id>) -> Result<&mut Animation, AnimationError> {
+        let index = self.animation_index(id)?;
+        Ok(&mut self.animations[index])
+    }
+
+    /// Adds an animation to the model, returns it's index
+    pub fn add_animation(&mut self, animation: Animation) -> usize {
+        let index = self.animations.len();
+        if let Some(name) = &animation.name {
+            if self.animation_names.insert(name.clone(), index).is_some() {
+                log::warn!("Duplicate animation name {:?}, using the last one", name);
+            }
+        }
+        self.animations.push(animation);
+        index
+    }
+
+    pub fn apply_animation(&mut self, id: impl Into<Id>, time: f32) -> Result<(), AnimationError> {
+        let index = self.animation_index(id)?;
+        self.animations[index].sample_into(time, &mut self.transforms, &mut self.weights);
+        self.apply_animation_properties(index, time)
+    }
+
+    /// Marks the animation as additive, relative to the reference pose.
+    /// Such animations can then be layered on top of other ones with [Model::apply_additive_animation]
+    pub fn make_additive(
+        &mut self,
+        id: impl Into<Id>,
+        reference: AdditiveReference,
+    ) -> Result<(), AnimationError> {
+        let index = self.animation_index(id)?;
+        let reference = match reference {
+            AdditiveReference::FirstFrame => index,
+            AdditiveReference::Animation(reference) => self.animation_index(reference)?,
+        };
+
+        let mut pose = self.rest_pose();
+        self.animations[reference].sample(0.0, &mut pose);
+        self.animations[index].additive = Some(pose);
+        Ok(())
+    }
+
+    /// Applies the difference between an additive animation at `time` and it's reference pose
+    /// on top of the current pose, scaled by `weight`. Animated properties are not applied
+    pub fn apply_additive_animation(
+        &mut self,
+        id: impl Into<Id>,
+        time: f32,
+        weight: f32,
+    ) -> Result<(), AnimationError> {
+        let index = self.animation_index(id)?;
+        let animation = &self.animations[index];
+        let Some(reference) = &animation.additive else {
+            return Err(AnimationError::NotAdditive(Id::Index(index)));
+        };
+
+        let mut additive = reference.clone();
+        animation.sample(time, &mut additive);
+        let mut pose = self.pose();
+        pose.add(&additive, reference, weight);
+        self.apply_pose(&pose);
+        Ok(())
+    }
+
+    /// Samples several animations, given as `(animation, time, weight)`, and blends them together.
+    /// Nodes that some animation doesn't animate, use their rest transform for it.
+    /// Translations and scales are blended linearly, rotations are blended as normalized quaternions.
+    /// Animated material, camera and light properties are blended linearly too
+    pub fn blend_animations<A: Into<Id>>(
+        &mut self,
+        animations: impl IntoIterator<Item = (A, f32, f32)>,
+    ) -> Result<(), AnimationError> {
+        let animations = self.animation_indices(animations)?;
+        if let Some(pose) = self.blended_pose(&animations) {
+            self.apply_pose(&pose);
+        }
+        self.apply_blended_properties(&animations);
+        Ok(())
+    }
+
+    /// Resolves ids of `(animation, time, weight)` triples into indices
+    pub(crate) fn animation_indices<A: Into<Id>>(
+        &self,
+        animations: impl IntoIterator<Item = (A, f32, f32)>,
+    ) -> Result<Vec<(usize, f32, f32)>, AnimationError> {
+        animations
+            .into_iter()
+            .map(|(id, time, weight)| Ok((self.animation_index(id)?, time, weight)))
+            .collect()
+    }
+
+    /// Samples and blends node transforms and weights of the animations, given as `(animation index, time, weight)`.
+    /// Returns [None] if there are no animations or their weights sum up to zero
+    pub(crate) fn blended_pose(&self, animations: &[(usize, f32, f32)]) -> Option<Pose> {
+        let samples = animations
+            .iter()
+            .map(|&(index, time, weight)| {
+                let mut pose = self.rest_pose();
+                self.animations[index].sample(time, &mut pose);
+                (pose, weight)
+            })
+            .collect_vec();
+        Pose::weighted(samples.iter().map(|(pose, weight)| (pose, *weight)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            self.transforms[channel.target] = transform * self.transforms[channel.target];
+    fn channel(interpolation: Interpolation, inputs: Vec<f32>) -> Channel {
+        Channel {
+            target: 0,
+            interpolation,
+            inputs,
+            outputs: Outputs::Translation(Vec::new()),
         }
     }
+
+    #[test]
+    fn step() {
+        let channel = channel(Interpolation::Step, vec![0.0, 1.0, 2.0]);
+        let outputs = [0.0, 1.0, 5.0];
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        assert_eq!(channel.sample(0.5, &outputs, lerp), 0.0);
+        assert_eq!(channel.sample(1.0, &outputs, lerp), 1.0);
+        assert_eq!(channel.sample(1.99, &outputs, lerp), 1.0);
+        assert_eq!(channel.sample(2.0, &outputs, lerp), 5.0);
+    }
+
+    #[test]
+    fn linear() {
+        let channel = channel(Interpolation::Linear, vec![1.0, 2.0, 4.0]);
+        let outputs = [0.0, 1.0, 5.0];
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        assert_eq!(channel.sample(1.25, &outputs, lerp), 0.25);
+        assert_eq!(channel.sample(3.0, &outputs, lerp), 3.0);
+        // Values are held outside of the keyframes
+        assert_eq!(channel.sample(0.0, &outputs, lerp), 0.0);
+        assert_eq!(channel.sample(10.0, &outputs, lerp), 5.0);
+    }
+
+    #[test]
+    fn cubic_spline() {
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
+        // Tangents of a line at constant speed, so the spline follows it exactly
+        let line = channel(Interpolation::CubicSpline, vec![0.0, 2.0]);
+        let outputs = [1.0, 0.0, 1.0, 1.0, 2.0, 1.0];
+        for time in [0.0, 0.5, 1.0, 1.5, 2.0] {
+            assert!((line.sample(time, &outputs, lerp) - time).abs() < 1e-5);
+        }
+
+        // Zero tangents ease in and out
+        let ease = channel(Interpolation::CubicSpline, vec![0.0, 1.0]);
+        let outputs = [0.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        assert!((ease.sample(0.25, &outputs, lerp) - 0.15625).abs() < 1e-5);
+        assert!((ease.sample(0.5, &outputs, lerp) - 0.5).abs() < 1e-5);
+        assert!((ease.sample(0.75, &outputs, lerp) - 0.84375).abs() < 1e-5);
+    }
+
+    #[test]
+    fn morph_weights() {
+        // Two outputs of three morph targets
+        let weights = [0.0, 0.1, 0.2, 1.0, 1.1, 1.2];
+        assert_eq!(
+            deinterleave_weights(&weights, 2),
+            vec![vec![0.0, 1.0], vec![0.1, 1.1], vec![0.2, 1.2]],
+        );
+    }
 }
@@ -0,0 +1,181 @@
+use super::*;
+
+/// Joint matrices of a skin, sampled from an animation at a fixed frame rate and stored in a float texture.
+/// Meshes can then be skinned in the shader with `baked_skin_matrix()` from the prelude,
+/// without uploading joint matrices for every instance, see [Model::draw_baked].
+///
+/// Every row of the texture is a frame, every joint takes 4 texels: the columns of its skinning matrix
+pub struct BakedAnimation {
+    pub texture: ugli::Texture,
+    /// Index of the baked skin in [Model::skins]
+    pub skin: usize,
+    pub joints: usize,
+    pub frames: usize,
+    pub frame_rate: f32,
+    pub duration: f32,
+}
+
+impl BakedAnimation {
+    /// Uniforms for `baked_skin_matrix()` in the prelude.
+    /// `time` is shared by all instances, each instance adds its own time offset in the shader
+    pub fn uniforms(&self, time: f32) -> impl ugli::Uniforms + '_ {
+        ugli::uniforms! {
+            u_baked_joints: &self.texture,
+            u_baked_size: vec2(self.joints * 4, self.frames).map(|x| x as f32),
+            u_baked_frame_rate: self.frame_rate,
+            u_baked_duration: self.duration,
+            u_baked_time: time,
+        }
+    }
+}
+
+impl Debug for BakedAnimation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BakedAnimation")
+            .field("skin", &self.skin)
+            .field("joints", &self.joints)
+            .field("frames", &self.frames)
+            .field("frame_rate", &self.frame_rate)
+            .field("duration", &self.duration)
+            .finish()
+    }
+}
+
+impl Model {
+    /// Finds index of a skin in [Model::skins]
+    pub fn skin_index(&self, id: impl Into<Id>) -> Result<usize, BakeError> {
+        let id = id.into();
+        let index = match &id {
+            Id::Index(index) => Some(*index).filter(|&index| index < self.skins.len()),
+            Id::Name(name) => self
+                .document
+                .skins()
+                .position(|skin| skin.name() == Some(name.as_str())),
+        };
+        index.ok_or(BakeError::SkinNotFound(id))
+    }
+
+    /// Samples the animation on top of the rest pose `frame_rate` times per second and bakes joint matrices
+    /// of the skin into a texture. Joint matrices are relative to the scene root,
+    /// current overrides are applied to every frame
+    pub fn bake_animation(
+        &self,
+        ugli: &Ugli,
+        id: impl Into<Id>,
+        skin: impl Into<Id>,
+        frame_rate: f32,
+    ) -> Result<BakedAnimation, BakeError> {
+        if frame_rate.is_nan() || frame_rate <= 0.0 {
+            return Err(BakeError::InvalidFrameRate(frame_rate));
+        }
+        let animation = self.animation(id)?;
+        let skin = self.skin_index(skin)?;
+        let skin_data = &self.skins[skin];
+        let parents = self.parents();
+
+        let duration = animation.duration();
+        let frames = (duration * frame_rate).ceil() as usize + 1;
+        let joints = skin_data.joints.len();
+
+        let mut data = Vec::with_capacity(frames * joints * 16 * std::mem::size_of::<f32>());
+        for frame in 0..frames {
+            let mut pose = self.rest_pose();
+            animation.sample((frame as f32 / frame_rate).min(duration), &mut pose);
+            let node_transforms = model_space_matrices(&pose.final_transforms(self), &parents);
+            for matrix in skin_data.joint_matrices(&node_transforms) {
+                for column in 0..4 {
+                    for row in 0..4 {
+                        data.extend(matrix[(row, column)].to_ne_bytes());
+                    }
+                }
+            }
+        }
+
+        let mut texture = ugli::Texture::from_raw(
+            ugli,
+            vec2(joints * 4, frames),
+            &data,
+            ugli::Format::RGBA,
+            ugli::Type::Float,
+            false,
+        );
+        texture.set_filter(ugli::Filter::Nearest);
+
+        Ok(BakedAnimation {
+            texture,
+            skin,
+            joints,
+            frames,
+            frame_rate,
+            duration,
+        })
+    }
+
+    /// Draws every instance of the meshes, that are skinned with the baked skin (each mesh once).
+    /// The program is expected to use `baked_skin_matrix(time_offset)` from the prelude,
+    /// with the time offset (and anything else, like placement) coming from the instance attributes
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_baked<I: ugli::Vertex>(
+        &self,
+        framebuffer: &mut ugli::Framebuffer,
+        program: &ugli::Program,
+        baked: &BakedAnimation,
+        time: f32,
+        instances: &ugli::VertexBuffer<I>,
+        uniforms: impl ugli::Uniforms,
+        draw_parameters: impl std::borrow::Borrow<ugli::DrawParameters>,
+    ) {
+        let draw_parameters = draw_parameters.borrow();
+
+        let skinned_meshes = self
+            .document
+            .nodes()
+            .filter(|node| node.skin().map(|skin| skin.index()) == Some(baked.skin))
+            .filter_map(|node| node.mesh().map(|mesh| (node.index(), mesh.index())))
+            .unique_by(|&(_, mesh)| mesh);
+
+        for (node, mesh) in skinned_meshes {
+            for mesh in &self.meshes[mesh] {
+                mesh.draw_instanced(
+                    framebuffer,
+                    program,
+                    instances,
+                    (
+                        ugli::SingleUniform::new("u_model_matrix", self.transform),
+                        morph_weights_uniforms(&self.weights[node]),
+                        baked.uniforms(time),
+                        self.materials[mesh.material].uniforms(),
+                        &uniforms,
+                    ),
+                    draw_parameters,
+                );
+            }
+        }
+    }
+}
+
+/// Transforms of all nodes relative to the scene root, as matrices
+fn model_space_matrices(transforms: &[Transform], parents: &[Option<usize>]) -> Vec<mat4<f32>> {
+    fn matrix(
+        node: usize,
+        transforms: &[Transform],
+        parents: &[Option<usize>],
+        matrices: &mut [Option<mat4<f32>>],
+    ) -> mat4<f32> {
+        if let Some(matrix) = matrices[node] {
+            return matrix;
+        }
+        let local = transforms[node].matrix();
+        let result = match parents[node] {
+            Some(parent) => local * matrix(parent, transforms, parents, matrices),
+            None => local,
+        };
+        matrices[node] = Some(result);
+        result
+    }
+
+    let mut matrices = vec![None; transforms.len()];
+    (0..transforms.len())
+        .map(|node| matrix(node, transforms, parents, &mut matrices))
+        .collect()
+}
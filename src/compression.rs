@@ -0,0 +1,303 @@
+use super::*;
+
+/// Settings of [Animation::compress]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompressionOptions {
+    /// Maximum error of translations and scales, and of morph target weights
+    pub tolerance: f32,
+    /// Maximum error of rotations
+    pub rotation_tolerance: Angle<f32>,
+    /// Store rotations as normalized i16 components, like glTF does with `SHORT` accessors.
+    /// Cubic spline channels are never quantized, since their tangents don't fit into -1..1
+    pub quantize_rotations: bool,
+}
+
+impl Default for CompressionOptions {
+    fn default() -> Self {
+        Self {
+            tolerance: 1e-4,
+            rotation_tolerance: Angle::from_degrees(0.05),
+            quantize_rotations: false,
+        }
+    }
+}
+
+/// Result of [Animation::compress]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompressionReport {
+    pub keyframes_before: usize,
+    pub keyframes_after: usize,
+    /// Memory used by keyframe times and values
+    pub bytes_before: usize,
+    pub bytes_after: usize,
+}
+
+impl CompressionReport {
+    pub fn bytes_saved(&self) -> usize {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+
+    /// Sums up reports of several animations
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            keyframes_before: self.keyframes_before + other.keyframes_before,
+            keyframes_after: self.keyframes_after + other.keyframes_after,
+            bytes_before: self.bytes_before + other.bytes_before,
+            bytes_after: self.bytes_after + other.bytes_after,
+        }
+    }
+}
+
+impl Animation {
+    /// Removes keyframes, that can be reconstructed within the tolerance by interpolating their neighbours,
+    /// and optionally quantizes rotations. Cubic spline channels keep all of their keyframes
+    pub fn compress(&mut self, options: &CompressionOptions) -> CompressionReport {
+        let mut report = CompressionReport::default();
+        for channel in &mut self.channels {
+            report.keyframes_before += channel.inputs.len();
+            report.bytes_before += channel.memory_size();
+            channel.compress(options);
+            report.keyframes_after += channel.inputs.len();
+            report.bytes_after += channel.memory_size();
+        }
+        report
+    }
+}
+
+impl Model {
+    /// Compresses all animations of the model, see [Animation::compress]
+    pub fn compress_animations(&mut self, options: &CompressionOptions) -> CompressionReport {
+        self.animations
+            .iter_mut()
+            .map(|animation| animation.compress(options))
+            .fold(CompressionReport::default(), CompressionReport::merge)
+    }
+}
+
+impl Channel {
+    /// Memory used by keyframe times and values, in bytes
+    pub fn memory_size(&self) -> usize {
+        use std::mem::size_of;
+        let outputs = match &self.outputs {
+            Outputs::Translation(values) | Outputs::Scale(values) => {
+                values.len() * size_of::<vec3<f32>>()
+            }
+            Outputs::Rotation(values) => values.len() * size_of::<vec4<f32>>(),
+            Outputs::QuantizedRotation(values) => values.len() * size_of::<[i16; 4]>(),
+            Outputs::Weights(outputs) | Outputs::Property { outputs, .. } => {
+                outputs.iter().map(Vec::len).sum::<usize>() * size_of::<f32>()
+            }
+        };
+        self.inputs.len() * size_of::<f32>() + outputs
+    }
+
+    fn compress(&mut self, options: &CompressionOptions) {
+        if self.interpolation != Interpolation::CubicSpline {
+            let kept = match &self.outputs {
+                Outputs::Translation(values) | Outputs::Scale(values) => {
+                    self.kept_keyframes(|a, b, index, t| {
+                        (self.interpolate(values, a, b, t, lerp) - values[index]).len()
+                            <= options.tolerance
+                    })
+                }
+                Outputs::Rotation(values) => self.kept_keyframes(|a, b, index, t| {
+                    rotation_error(self.interpolate(values, a, b, t, slerp), values[index])
+                        <= options.rotation_tolerance.as_radians()
+                }),
+                Outputs::QuantizedRotation(values) => {
+                    let values = values
+                        .iter()
+                        .copied()
+                        .map(dequantize_rotation)
+                        .collect_vec();
+                    self.kept_keyframes(|a, b, index, t| {
+                        rotation_error(self.interpolate(&values, a, b, t, slerp), values[index])
+                            <= options.rotation_tolerance.as_radians()
+                    })
+                }
+                Outputs::Weights(outputs) | Outputs::Property { outputs, .. } => self
+                    .kept_keyframes(|a, b, index, t| {
+                        outputs.iter().all(|values| {
+                            (self.interpolate(values, a, b, t, lerp_f32) - values[index]).abs()
+                                <= options.tolerance
+                        })
+                    }),
+            };
+
+            self.inputs = select(&self.inputs, &kept);
+            match &mut self.outputs {
+                Outputs::Translation(values) | Outputs::Scale(values) => {
+                    *values = select(values, &kept)
+                }
+                Outputs::Rotation(values) => *values = select(values, &kept),
+                Outputs::QuantizedRotation(values) => *values = select(values, &kept),
+                Outputs::Weights(outputs) | Outputs::Property { outputs, .. } => {
+                    for values in outputs {
+                        *values = select(values, &kept);
+                    }
+                }
+            }
+        }
+
+        if options.quantize_rotations && self.interpolation != Interpolation::CubicSpline {
+            if let Outputs::Rotation(values) = &self.outputs {
+                self.outputs = Outputs::QuantizedRotation(
+                    values.iter().copied().map(quantize_rotation).collect(),
+                );
+            }
+        }
+    }
+
+    /// Value between keyframes `a` and `b`, as it would be sampled if there were no keyframes in between
+    fn interpolate<T: Copy>(
+        &self,
+        values: &[T],
+        a: usize,
+        b: usize,
+        t: f32,
+        lerp: impl Fn(T, T, f32) -> T,
+    ) -> T {
+        match self.interpolation {
+            Interpolation::Step => values[a],
+            _ => lerp(values[a], values[b], t),
+        }
+    }
+
+    /// Greedily finds keyframes, that have to be kept. `fits(a, b, index, t)` checks,
+    /// that the keyframe `index` is reconstructed by interpolating between `a` and `b` with factor `t`
+    fn kept_keyframes(&self, fits: impl Fn(usize, usize, usize, f32) -> bool) -> Vec<usize> {
+        let count = self.inputs.len();
+        if count <= 2 {
+            return (0..count).collect();
+        }
+
+        let mut kept = vec![0];
+        let mut anchor = 0;
+        for next in 2..count {
+            let duration = self.inputs[next] - self.inputs[anchor];
+            let all_fit = (anchor + 1..next).all(|index| {
+                let t = if duration > 0.0 {
+                    (self.inputs[index] - self.inputs[anchor]) / duration
+                } else {
+                    0.0
+                };
+                fits(anchor, next, index, t)
+            });
+            if !all_fit {
+                anchor = next - 1;
+                kept.push(anchor);
+            }
+        }
+        kept.push(count - 1);
+        kept
+    }
+}
+
+fn select<T: Copy>(values: &[T], indices: &[usize]) -> Vec<T> {
+    indices.iter().map(|&index| values[index]).collect()
+}
+
+/// Angle between two rotations, in radians
+fn rotation_error(a: vec4<f32>, b: vec4<f32>) -> f32 {
+    2.0 * vec4::dot(normalize_quat(a), normalize_quat(b))
+        .abs()
+        .min(1.0)
+        .acos()
+}
+
+pub(crate) fn quantize_rotation(rotation: vec4<f32>) -> [i16; 4] {
+    let rotation = normalize_quat(rotation);
+    [rotation.x, rotation.y, rotation.z, rotation.w]
+        .map(|value| (value.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16)
+}
+
+pub(crate) fn dequantize_rotation(rotation: [i16; 4]) -> vec4<f32> {
+    let [x, y, z, w] = rotation.map(|value| (value as f32 / i16::MAX as f32).max(-1.0));
+    normalize_quat(vec4(x, y, z, w))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn animation(interpolation: Interpolation, outputs: Outputs, keyframes: usize) -> Animation {
+        Animation::new(vec![Channel {
+            target: 0,
+            interpolation,
+            inputs: (0..keyframes).map(|index| index as f32 * 0.1).collect(),
+            outputs,
+        }])
+    }
+
+    fn sample(animation: &Animation, time: f32) -> Transform {
+        let mut transform = Transform::identity();
+        animation.sample_node(time, 0, &mut transform);
+        transform
+    }
+
+    #[test]
+    fn linear_keyframes_are_removed() {
+        let translations = (0..10).map(|index| vec3(index as f32, 0.0, 0.0)).collect();
+        let mut compressed = animation(
+            Interpolation::Linear,
+            Outputs::Translation(translations),
+            10,
+        );
+        let original = compressed.clone();
+
+        let report = compressed.compress(&CompressionOptions::default());
+        assert_eq!(report.keyframes_before, 10);
+        assert_eq!(report.keyframes_after, 2);
+        assert!(report.bytes_saved() > 0);
+        for step in 0..=90 {
+            let time = step as f32 * 0.01;
+            let error = sample(&compressed, time).translation - sample(&original, time).translation;
+            assert!(error.len() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn changes_are_kept() {
+        let scales = [1.0, 1.0, 1.0, 2.0, 2.0]
+            .map(|scale| vec3(scale, scale, scale))
+            .to_vec();
+        let mut compressed = animation(Interpolation::Linear, Outputs::Scale(scales), 5);
+        compressed.compress(&CompressionOptions::default());
+        assert_eq!(compressed.channels[0].inputs.len(), 4);
+        assert_eq!(sample(&compressed, 0.2).scale, vec3(1.0, 1.0, 1.0));
+        assert_eq!(sample(&compressed, 0.3).scale, vec3(2.0, 2.0, 2.0));
+    }
+
+    #[test]
+    fn quantized_rotations() {
+        let rotations = (0..10)
+            .map(|index| quat_from_axis_angle(vec3(0.0, 1.0, 0.0), (index as f32 * 0.7).sin()))
+            .collect_vec();
+        for &rotation in &rotations {
+            let restored = dequantize_rotation(quantize_rotation(rotation));
+            assert!(vec4::dot(rotation, restored).abs() > 1.0 - 1e-6);
+        }
+
+        let mut compressed = animation(Interpolation::Linear, Outputs::Rotation(rotations), 10);
+        let original = compressed.clone();
+        compressed.compress(&CompressionOptions {
+            quantize_rotations: true,
+            ..CompressionOptions::default()
+        });
+        assert!(matches!(
+            compressed.channels[0].outputs,
+            Outputs::QuantizedRotation(_)
+        ));
+        let tolerance = CompressionOptions::default()
+            .rotation_tolerance
+            .as_radians();
+        for step in 0..=90 {
+            let time = step as f32 * 0.01;
+            let a = sample(&compressed, time).rotation;
+            let b = sample(&original, time).rotation;
+            // Angle between rotations, with some room for quantization
+            let angle = 2.0 * vec4::dot(a, b).abs().min(1.0).acos();
+            assert!(angle < tolerance * 2.0, "{} at {}", angle, time);
+        }
+    }
+}
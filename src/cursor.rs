@@ -0,0 +1,135 @@
+use super::*;
+
+/// How many keyframes a cursor steps over before giving up and doing a binary search
+const MAX_CURSOR_STEPS: usize = 8;
+
+/// Remembers the last keyframe of every channel of an animation. When the animation is played forward,
+/// keyframes are found by stepping from the remembered ones instead of a binary search over all of them.
+/// Seeking backwards (e.g. looping) falls back to the search, so a cursor is always safe to use
+#[derive(Debug, Clone, Default)]
+pub struct AnimationCursor {
+    keyframes: Vec<usize>,
+}
+
+impl AnimationCursor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets all remembered keyframes
+    pub fn reset(&mut self) {
+        self.keyframes.clear();
+    }
+}
+
+impl Animation {
+    /// Like [Animation::sample], but uses the cursor to find keyframes
+    pub fn sample_cached(&self, time: f32, pose: &mut Pose, cursor: &mut AnimationCursor) {
+        self.sample_into_cached(time, &mut pose.transforms, &mut pose.weights, cursor);
+    }
+
+    pub(crate) fn sample_into_cached(
+        &self,
+        time: f32,
+        transforms: &mut [Transform],
+        weights: &mut [Vec<f32>],
+        cursor: &mut AnimationCursor,
+    ) {
+        cursor.keyframes.resize(self.channels.len(), 0);
+        self.sample_keyframes_into(
+            |index, channel| channel.keyframes_cached(time, &mut cursor.keyframes[index]),
+            transforms,
+            weights,
+        );
+    }
+}
+
+impl Channel {
+    /// Like [Channel::keyframes], but starts from the keyframe, that was found last time.
+    /// `cursor` is the index of the last keyframe at or before `time`
+    fn keyframes_cached(&self, time: f32, cursor: &mut usize) -> (usize, usize, f32) {
+        let count = self.inputs.len();
+        if count == 0 {
+            return (0, 0, 0.0);
+        }
+
+        let search = || {
+            self.inputs
+                .partition_point(|&t| t <= time)
+                .saturating_sub(1)
+        };
+        let mut index = *cursor;
+        if index >= count || self.inputs[index] > time {
+            index = search();
+        } else {
+            let mut steps = 0;
+            while index + 1 < count && self.inputs[index + 1] <= time {
+                index += 1;
+                steps += 1;
+                if steps == MAX_CURSOR_STEPS {
+                    index = search();
+                    break;
+                }
+            }
+        }
+        *cursor = index;
+
+        let prev = self.inputs[index];
+        if time <= prev || index + 1 == count {
+            (index, index, 0.0)
+        } else {
+            let next = self.inputs[index + 1];
+            (index, index + 1, (time - prev) / (next - prev))
+        }
+    }
+}
+
+impl Model {
+    /// Like [Model::apply_animation], but uses the cursor to find keyframes
+    pub fn apply_animation_cached(
+        &mut self,
+        id: impl Into<Id>,
+        time: f32,
+        cursor: &mut AnimationCursor,
+    ) -> Result<(), AnimationError> {
+        let index = self.animation_index(id)?;
+        self.animations[index].sample_into_cached(
+            time,
+            &mut self.transforms,
+            &mut self.weights,
+            cursor,
+        );
+        self.apply_animation_properties(index, time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_as_binary_search() {
+        let animation = Animation::new(vec![Channel {
+            target: 0,
+            interpolation: Interpolation::Linear,
+            inputs: (0..100).map(|index| index as f32 * 0.1).collect(),
+            outputs: Outputs::Translation(
+                (0..100)
+                    .map(|index| vec3((index as f32).sin(), 0.0, 0.0))
+                    .collect(),
+            ),
+        }]);
+        let mut cursor = AnimationCursor::new();
+        // Forward playback, large jumps, looping back and times outside of the keyframes
+        let times = (0..300)
+            .map(|step| step as f32 * 0.016)
+            .chain([9.5, 2.0, 2.05, 7.3, -1.0, 0.0, 20.0, 0.3]);
+        for time in times {
+            let mut expected = Pose::new(vec![Transform::identity()], vec![Vec::new()]);
+            let mut pose = expected.clone();
+            animation.sample(time, &mut expected);
+            animation.sample_cached(time, &mut pose, &mut cursor);
+            assert_eq!(pose, expected, "at {}", time);
+        }
+    }
+}
@@ -0,0 +1,463 @@
+use super::*;
+
+/// Analytic IK for a chain of two bones (root -> middle -> end), like a leg or an arm.
+///
+/// IK solvers modify local rotations in [Model::transforms], so solve them after applying animations.
+/// Targets and poles are in model space (relative to the scene root nodes, [Model::transform] is not applied).
+/// Solvers see the transforms with overrides applied, but an override, that replaces the rotation
+/// of a solved node (see [TransformOverride::rotation]), takes precedence, so IK can't rotate that node.
+/// Nodes are resolved when solving, so solvers can be created without a model
+#[derive(Debug, Clone, PartialEq)]
+pub struct TwoBoneIk {
+    pub root: Id,
+    pub middle: Id,
+    pub end: Id,
+    pub target: vec3<f32>,
+    /// Point, that the middle joint (knee or elbow) should bend towards
+    pub pole: Option<vec3<f32>>,
+    pub weight: f32,
+}
+
+impl TwoBoneIk {
+    pub fn new(
+        root: impl Into<Id>,
+        middle: impl Into<Id>,
+        end: impl Into<Id>,
+        target: vec3<f32>,
+    ) -> Self {
+        Self {
+            root: root.into(),
+            middle: middle.into(),
+            end: end.into(),
+            target,
+            pole: None,
+            weight: 1.0,
+        }
+    }
+
+    /// Chain ending at the `end` node, with it's parent and grandparent as the other joints
+    pub fn from_end(
+        model: &Model,
+        end: impl Into<Id>,
+        target: vec3<f32>,
+    ) -> Result<Self, NodeError> {
+        let end = model.node_index(end)?;
+        let parents = model.parents();
+        let middle = parents[end].ok_or(NodeError::NoParent(Id::Index(end)))?;
+        let root = parents[middle].ok_or(NodeError::NoParent(Id::Index(middle)))?;
+        Ok(Self::new(root, middle, end, target))
+    }
+
+    pub fn with_pole(self, pole: vec3<f32>) -> Self {
+        Self {
+            pole: Some(pole),
+            ..self
+        }
+    }
+
+    pub fn with_weight(self, weight: f32) -> Self {
+        Self { weight, ..self }
+    }
+
+    pub fn solve(&self, model: &mut Model) -> Result<(), NodeError> {
+        let root = model.node_index(self.root.clone())?;
+        let middle = model.node_index(self.middle.clone())?;
+        let end = model.node_index(self.end.clone())?;
+        let parents = model.parents();
+        let original = [root, middle].map(|node| (node, model.transforms[node].rotation));
+        let position =
+            |model: &Model, node: usize| model_space_transform(model, &parents, node).translation;
+
+        let (a, b, c) = (
+            position(model, root),
+            position(model, middle),
+            position(model, end),
+        );
+        let upper = (b - a).len();
+        let lower = (c - b).len();
+        if upper < 1e-6 || lower < 1e-6 {
+            return Ok(());
+        }
+
+        // Bend the middle joint, so that the chain is as long as the distance to the target
+        let distance = (self.target - a)
+            .len()
+            .clamp(1e-4, (upper + lower) * (1.0 - 1e-4));
+        let current_angle = angle_between(a - b, c - b);
+        let target_angle = ((upper * upper + lower * lower - distance * distance)
+            / (2.0 * upper * lower))
+            .clamp(-1.0, 1.0)
+            .acos();
+        let mut axis = vec3::cross(a - b, c - b);
+        if axis.len() < 1e-6 {
+            // The chain is straight, so any bending direction will do
+            axis = vec3::cross(c - a, self.pole.unwrap_or(b) - a);
+            if axis.len() < 1e-6 {
+                axis = perpendicular(c - a);
+            }
+        }
+        let axis = axis * (1.0 / axis.len());
+        rotate_in_model_space(
+            model,
+            &parents,
+            middle,
+            quat_from_axis_angle(axis, target_angle - current_angle),
+        );
+
+        // Point the chain at the target
+        let c = position(model, end);
+        if (c - a).len() > 1e-6 && (self.target - a).len() > 1e-6 {
+            rotate_in_model_space(model, &parents, root, quat_between(c - a, self.target - a));
+        }
+
+        // Twist the chain around it's axis, so that the middle joint points at the pole
+        if let Some(pole) = self.pole {
+            let c = position(model, end);
+            let b = position(model, middle);
+            if (c - a).len() > 1e-6 {
+                let axis = (c - a) * (1.0 / (c - a).len());
+                let project = |v: vec3<f32>| v - axis * vec3::dot(v, axis);
+                let (bend, pole) = (project(b - a), project(pole - a));
+                if bend.len() > 1e-6 && pole.len() > 1e-6 {
+                    let angle =
+                        vec3::dot(vec3::cross(bend, pole), axis).atan2(vec3::dot(bend, pole));
+                    rotate_in_model_space(model, &parents, root, quat_from_axis_angle(axis, angle));
+                }
+            }
+        }
+
+        blend_rotations(model, &original, self.weight);
+        Ok(())
+    }
+}
+
+/// Turns a node (head, eye, turret) so that it's `forward` axis points at the target
+#[derive(Debug, Clone, PartialEq)]
+pub struct LookAt {
+    pub node: Id,
+    pub target: vec3<f32>,
+    /// Axis of the node in it's local space, that should point at the target. Defaults to +Z
+    pub forward: vec3<f32>,
+    /// Maximum angle between the animated direction and the target direction
+    pub max_angle: Option<Angle<f32>>,
+    pub weight: f32,
+}
+
+impl LookAt {
+    pub fn new(node: impl Into<Id>, target: vec3<f32>) -> Self {
+        Self {
+            node: node.into(),
+            target,
+            forward: vec3(0.0, 0.0, 1.0),
+            max_angle: None,
+            weight: 1.0,
+        }
+    }
+
+    pub fn with_forward(self, forward: vec3<f32>) -> Self {
+        Self { forward, ..self }
+    }
+
+    pub fn with_max_angle(self, max_angle: Angle<f32>) -> Self {
+        Self {
+            max_angle: Some(max_angle),
+            ..self
+        }
+    }
+
+    pub fn with_weight(self, weight: f32) -> Self {
+        Self { weight, ..self }
+    }
+
+    pub fn solve(&self, model: &mut Model) -> Result<(), NodeError> {
+        let node = model.node_index(self.node.clone())?;
+        let parents = model.parents();
+        let transform = model_space_transform(model, &parents, node);
+        let forward = quat_rotate(transform.rotation, self.forward);
+        let direction = self.target - transform.translation;
+        if forward.len() < 1e-6 || direction.len() < 1e-6 {
+            return Ok(());
+        }
+
+        let mut rotation = quat_between(forward, direction);
+        if let Some(max_angle) = self.max_angle {
+            let angle = angle_between(forward, direction);
+            let max_angle = max_angle.as_radians();
+            if angle > max_angle {
+                rotation = slerp(vec4(0.0, 0.0, 0.0, 1.0), rotation, max_angle / angle);
+            }
+        }
+        rotation = slerp(vec4(0.0, 0.0, 0.0, 1.0), rotation, self.weight);
+        rotate_in_model_space(model, &parents, node, rotation);
+        Ok(())
+    }
+}
+
+/// Iterative IK for a chain of any length (tails, tentacles, spines) using the FABRIK algorithm
+#[derive(Debug, Clone, PartialEq)]
+pub struct FabrikChain {
+    /// Nodes of the chain, from the root to the end
+    pub nodes: Vec<Id>,
+    pub target: vec3<f32>,
+    pub iterations: usize,
+    /// Distance to the target, that is close enough to stop iterating
+    pub tolerance: f32,
+    pub weight: f32,
+}
+
+impl FabrikChain {
+    pub fn new(nodes: impl IntoIterator<Item = impl Into<Id>>, target: vec3<f32>) -> Self {
+        Self {
+            nodes: nodes.into_iter().map(Into::into).collect(),
+            target,
+            iterations: 10,
+            tolerance: 1e-3,
+            weight: 1.0,
+        }
+    }
+
+    /// Chain from the `root` node down to it's descendant `end`
+    pub fn from_nodes(
+        model: &Model,
+        root: impl Into<Id>,
+        end: impl Into<Id>,
+        target: vec3<f32>,
+    ) -> Result<Self, NodeError> {
+        let root = model.node_index(root)?;
+        let end = model.node_index(end)?;
+        let parents = model.parents();
+        let mut nodes = vec![end];
+        let mut node = end;
+        while node != root {
+            node =
+                parents[node].ok_or(NodeError::NotDescendant(Id::Index(end), Id::Index(root)))?;
+            nodes.push(node);
+        }
+        nodes.reverse();
+        Ok(Self::new(nodes, target))
+    }
+
+    pub fn with_iterations(self, iterations: usize) -> Self {
+        Self { iterations, ..self }
+    }
+
+    pub fn with_tolerance(self, tolerance: f32) -> Self {
+        Self { tolerance, ..self }
+    }
+
+    pub fn with_weight(self, weight: f32) -> Self {
+        Self { weight, ..self }
+    }
+
+    pub fn solve(&self, model: &mut Model) -> Result<(), NodeError> {
+        if self.nodes.len() < 2 {
+            return Ok(());
+        }
+        let nodes: Vec<usize> = self
+            .nodes
+            .iter()
+            .map(|node| model.node_index(node.clone()))
+            .collect::<Result<_, _>>()?;
+        let parents = model.parents();
+        let original = nodes
+            .iter()
+            .map(|&node| (node, model.transforms[node].rotation))
+            .collect_vec();
+
+        let mut positions = nodes
+            .iter()
+            .map(|&node| model_space_transform(model, &parents, node).translation)
+            .collect_vec();
+        let lengths = positions
+            .iter()
+            .tuple_windows()
+            .map(|(&a, &b)| (b - a).len())
+            .collect_vec();
+        let root = positions[0];
+        let last = positions.len() - 1;
+
+        // Moves `position` along the line to `anchor`, so that it's at `length` from it
+        let constrain = |anchor: vec3<f32>, position: vec3<f32>, length: f32| {
+            let direction = position - anchor;
+            if direction.len() < 1e-6 {
+                position
+            } else {
+                anchor + direction * (length / direction.len())
+            }
+        };
+
+        if (self.target - root).len() >= lengths.iter().sum::<f32>() {
+            // Unreachable, stretch towards the target
+            for i in 0..last {
+                positions[i + 1] = constrain(positions[i], self.target, lengths[i]);
+            }
+        } else {
+            for _ in 0..self.iterations {
+                if (positions[last] - self.target).len() <= self.tolerance {
+                    break;
+                }
+                positions[last] = self.target;
+                for i in (0..last).rev() {
+                    positions[i] = constrain(positions[i + 1], positions[i], lengths[i]);
+                }
+                positions[0] = root;
+                for i in 0..last {
+                    positions[i + 1] = constrain(positions[i], positions[i + 1], lengths[i]);
+                }
+            }
+        }
+
+        // Rotate the joints one by one, from the root, to match the solved positions
+        for i in 0..last {
+            let from = model_space_transform(model, &parents, nodes[i]).translation;
+            let to = model_space_transform(model, &parents, nodes[i + 1]).translation;
+            let (current, solved) = (to - from, positions[i + 1] - positions[i]);
+            if current.len() > 1e-6 && solved.len() > 1e-6 {
+                rotate_in_model_space(model, &parents, nodes[i], quat_between(current, solved));
+            }
+        }
+
+        blend_rotations(model, &original, self.weight);
+        Ok(())
+    }
+}
+
+/// Applies a rotation, given in model space, to the node
+fn rotate_in_model_space(
+    model: &mut Model,
+    parents: &[Option<usize>],
+    node: usize,
+    rotation: vec4<f32>,
+) {
+    if let Some(TransformOverride::Absolute {
+        rotation: Some(_), ..
+    }) = model.overrides[node]
+    {
+        log::warn!(
+            "Node #{} has an override, that replaces it's rotation, so IK can't rotate it",
+            node
+        );
+    }
+    let parent = match parents[node] {
+        Some(parent) => model_space_transform(model, parents, parent).rotation,
+        None => vec4(0.0, 0.0, 0.0, 1.0),
+    };
+    let local = &mut model.transforms[node].rotation;
+    *local = normalize_quat(quat_mul(
+        quat_conjugate(parent),
+        quat_mul(rotation, quat_mul(parent, *local)),
+    ));
+}
+
+/// Blends solved rotations of the nodes with their rotations before solving
+fn blend_rotations(model: &mut Model, original: &[(usize, vec4<f32>)], weight: f32) {
+    if weight >= 1.0 {
+        return;
+    }
+    for &(node, rotation) in original {
+        let solved = &mut model.transforms[node].rotation;
+        *solved = slerp(rotation, *solved, weight.max(0.0));
+    }
+}
+
+fn angle_between(a: vec3<f32>, b: vec3<f32>) -> f32 {
+    (vec3::dot(a, b) / (a.len() * b.len()))
+        .clamp(-1.0, 1.0)
+        .acos()
+}
+
+fn perpendicular(v: vec3<f32>) -> vec3<f32> {
+    if v.x.abs() < 0.9 {
+        vec3::cross(v, vec3(1.0, 0.0, 0.0))
+    } else {
+        vec3::cross(v, vec3(0.0, 1.0, 0.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Model without meshes, with a chain of three nodes going up by 1 along the Y axis
+    fn chain() -> Model {
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "scene": 0,
+            "scenes": [{ "nodes": [0] }],
+            "nodes": [
+                { "name": "Root", "children": [1] },
+                { "name": "Middle", "translation": [0, 1, 0], "children": [2] },
+                { "name": "End", "translation": [0, 1, 0] }
+            ]
+        }"#;
+        let document = gltf::Gltf::from_slice(json.as_bytes()).unwrap().document;
+        let rest_transforms = document
+            .nodes()
+            .map(|node| Transform::from(node.transform()))
+            .collect_vec();
+        Model {
+            document,
+            scene: Some(0),
+            parentless_nodes: vec![0],
+
+            cameras: Vec::new(),
+            lights: Vec::new(),
+            meshes: Vec::new(),
+            materials: Vec::new(),
+            skins: Vec::new(),
+            animations: Vec::new(),
+            animation_names: HashMap::new(),
+
+            transform: mat4::identity(),
+            transforms: rest_transforms.clone(),
+            overrides: vec![None; 3],
+            rest_transforms,
+            default_weights: vec![Vec::new(); 3],
+            weights: vec![Vec::new(); 3],
+            rest_properties: HashMap::new(),
+        }
+    }
+
+    fn position(model: &Model, node: &str) -> vec3<f32> {
+        model.model_space_transform(node).unwrap().translation
+    }
+
+    #[test]
+    fn two_bone_reach() {
+        let mut model = chain();
+        let target = vec3(1.0, 1.0, 0.0);
+        TwoBoneIk::from_end(&model, "End", target)
+            .unwrap()
+            .solve(&mut model)
+            .unwrap();
+        let [root, middle, end] = ["Root", "Middle", "End"].map(|node| position(&model, node));
+        assert!((end - target).len() < 1e-4);
+        // Bones keep their lengths
+        assert!(((middle - root).len() - 1.0).abs() < 1e-4);
+        assert!(((end - middle).len() - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn two_bone_out_of_reach() {
+        let mut model = chain();
+        TwoBoneIk::new("Root", "Middle", "End", vec3(0.0, 0.0, 5.0))
+            .solve(&mut model)
+            .unwrap();
+        // Stretched towards the target
+        let end = position(&model, "End");
+        assert!((end - vec3(0.0, 0.0, 2.0)).len() < 1e-2);
+    }
+
+    #[test]
+    fn missing_nodes() {
+        let mut model = chain();
+        assert!(matches!(
+            TwoBoneIk::new("Root", "Knee", "End", vec3::ZERO).solve(&mut model),
+            Err(NodeError::NotFound(Id::Name(name))) if name == "Knee"
+        ));
+        assert!(matches!(
+            TwoBoneIk::from_end(&model, "Middle", vec3::ZERO),
+            Err(NodeError::NoParent(Id::Index(0)))
+        ));
+    }
+}
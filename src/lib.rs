@@ -1,54 +1,122 @@
 use geng::prelude::{itertools::Itertools, *};
 
 pub use animation::*;
+pub use bake::*;
 pub use camera::*;
+pub use compression::*;
+pub use cursor::*;
+pub use ik::*;
+pub use light::*;
+pub use mask::*;
 pub use material::*;
 pub use mesh::*;
+pub use mirror::*;
+pub use node::*;
+pub use overrides::*;
+pub use player::*;
+pub use pointer::*;
+pub use pose::*;
+pub use retarget::*;
+pub use root_motion::*;
+pub use scene::*;
 pub use skin::*;
+pub use state_machine::*;
+pub use sub_clip::*;
+pub use transform::*;
 
 mod animation;
+mod bake;
 mod camera;
+mod compression;
+mod cursor;
+mod ik;
+mod light;
+mod mask;
 mod material;
 mod mesh;
+mod mirror;
+mod node;
+mod overrides;
+mod player;
+mod pointer;
+mod pose;
+mod retarget;
+mod root_motion;
+mod scene;
 mod skin;
+mod state_machine;
+mod sub_clip;
+mod transform;
 
 pub struct Model {
     pub document: gltf::Document,
+    /// Index of the drawn scene (see [Model::set_scene]), [None] if the document has no scenes
+    pub scene: Option<usize>,
+    /// Nodes without a parent, which are drawn as roots when there are no scenes
+    parentless_nodes: Vec<usize>,
 
     pub cameras: Vec<Projection>,
-    pub meshes: Vec<Mesh>,
+    pub lights: Vec<Light>,
+    /// Primitives of every glTF mesh
+    pub meshes: Vec<Vec<Mesh>>,
     pub materials: Vec<Material>,
     pub skins: Vec<Skin>,
-    pub animations: HashMap<String, Animation>,
+    pub animations: Vec<Animation>,
+    /// Indices of named animations in [Model::animations]
+    pub animation_names: HashMap<String, usize>,
 
     pub transform: mat4<f32>,
-    pub transforms: Vec<mat4<f32>>,
+    /// Local transforms of every node, animations write into these
+    pub transforms: Vec<Transform>,
+    /// Local transforms of every node, as they are in the glTF file
+    pub rest_transforms: Vec<Transform>,
+    /// Procedural overrides of every node, applied on top of [Model::transforms]
+    /// when drawing, solving IK and baking
+    pub overrides: Vec<Option<TransformOverride>>,
+    /// Morph target weights of every node (empty for nodes without a mesh), when not animated
+    pub default_weights: Vec<Vec<f32>>,
+    /// Current (animated) morph target weights of every node
+    pub weights: Vec<Vec<f32>>,
+    /// Values of material, camera and light properties from before they were animated,
+    /// restored by [Model::reset_transforms]
+    rest_properties: HashMap<AnimationPointer, Vec<f32>>,
 }
 
 impl Model {
     pub fn load(ugli: &Ugli, path: impl AsRef<std::path::Path>) -> Result<Self, MeshLoadError> {
-        let gltf = gltf::import(path)?;
-        Self::from_gltf(ugli, gltf)
+        let (gltf, pointers) = import_gltf_path(path.as_ref())?;
+        Self::from_gltf_with_pointers(ugli, gltf, &pointers)
     }
 
     pub fn from_slice(ugli: &Ugli, bytes: impl AsRef<[u8]>) -> Result<Self, MeshLoadError> {
-        let gltf = gltf::import_slice(bytes)?;
-        Self::from_gltf(ugli, gltf)
+        let (gltf, pointers) = import_gltf(bytes.as_ref(), None)?;
+        Self::from_gltf_with_pointers(ugli, gltf, &pointers)
     }
 
+    /// Loads the model from an already imported glTF file.
+    /// `KHR_animation_pointer` channels can't be imported by the gltf crate, use [Model::load] for them
     pub fn from_gltf(
         ugli: &Ugli,
-        (document, buffers, images): (
+        gltf: (
             gltf::Document,
             Vec<gltf::buffer::Data>,
             Vec<gltf::image::Data>,
         ),
     ) -> Result<Self, MeshLoadError> {
-        if document.default_scene().is_none() {
-            return Err(MeshLoadError::NoDefaultScene);
-        }
+        Self::from_gltf_with_pointers(ugli, gltf, &PointerChannels::default())
+    }
 
-        debug_node_tree(document.default_scene().unwrap().nodes());
+    fn from_gltf_with_pointers(
+        ugli: &Ugli,
+        (document, buffers, images): (
+            gltf::Document,
+            Vec<gltf::buffer::Data>,
+            Vec<gltf::image::Data>,
+        ),
+        pointers: &PointerChannels,
+    ) -> Result<Self, MeshLoadError> {
+        let scene = default_scene(&document);
+        let parentless_nodes = parentless_nodes(&document);
 
         let mut materials = Vec::new();
         for material in document.materials() {
@@ -58,6 +126,7 @@ impl Model {
         let mut meshes = Vec::new();
         for mesh in document.meshes() {
             log::trace!("Loading mesh {:?}", mesh.name());
+            let mut primitives = Vec::new();
             for primitive in mesh.primitives() {
                 let material = match primitive.material().index() {
                     Some(index) => index,
@@ -71,46 +140,102 @@ impl Model {
                         materials.len() - 1
                     }
                 };
-                meshes.push(Mesh::load(ugli, primitive, &buffers, material)?);
+                primitives.push(Mesh::load(
+                    ugli,
+                    primitive,
+                    &buffers,
+                    material,
+                    mesh.index(),
+                )?);
             }
+            meshes.push(primitives);
         }
 
+        // Nodes can share a mesh, but have different weights
+        let default_weights = document
+            .nodes()
+            .map(|node| {
+                let Some(mesh) = node.mesh() else {
+                    return Vec::new();
+                };
+                match node.weights().or(mesh.weights()) {
+                    Some(weights) => weights.to_vec(),
+                    None => {
+                        let targets = mesh
+                            .primitives()
+                            .map(|primitive| primitive.morph_targets().len())
+                            .max()
+                            .unwrap_or(0);
+                        vec![0.0; targets]
+                    }
+                }
+            })
+            .collect_vec();
+
         let mut cameras = Vec::new();
         for camera in document.cameras() {
             cameras.push(Projection::from(camera.projection()));
         }
 
+        let lights = document
+            .lights()
+            .map_or_else(Vec::new, |lights| lights.map(Light::from).collect());
+
         let mut skins = Vec::new();
         for skin in document.skins() {
             skins.push(Skin::load(skin, &buffers)?);
         }
 
-        let mut animations = HashMap::new();
+        let mut animations = Vec::new();
         for animation in document.animations() {
-            if let Some(name) = animation.name() {
-                animations.insert(name.to_owned(), Animation::load(animation, &buffers)?);
-            } else {
-                log::warn!(
-                    "Unnamed animations are not supported yet. Skipping animation id {}",
-                    animation.index()
-                );
+            let pointers = pointers.get(animation.index());
+            animations.push(Animation::load_with_pointers(
+                animation, &buffers, pointers,
+            )?);
+        }
+        // Sub-clips go after all glTF animations, so that their indices stay the same as in the file
+        let sub_clips = animations
+            .iter()
+            .flat_map(|animation| animation.clips.iter().map(|clip| animation.sub_clip(clip)))
+            .collect_vec();
+        animations.extend(sub_clips);
+        let mut animation_names = HashMap::new();
+        for (index, animation) in animations.iter().enumerate() {
+            if let Some(name) = &animation.name {
+                if animation_names.insert(name.clone(), index).is_some() {
+                    log::warn!("Duplicate animation name {:?}, using the last one", name);
+                }
             }
         }
 
-        let transforms = vec![mat4::identity(); document.nodes().count()];
+        let rest_transforms = document
+            .nodes()
+            .map(|node| Transform::from(node.transform()))
+            .collect_vec();
 
-        Ok(Self {
+        let model = Self {
             document,
+            scene,
+            parentless_nodes,
 
             cameras,
+            lights,
             meshes,
             materials,
             skins,
             animations,
+            animation_names,
 
             transform: mat4::identity(),
-            transforms,
-        })
+            transforms: rest_transforms.clone(),
+            overrides: vec![None; rest_transforms.len()],
+            rest_transforms,
+            weights: default_weights.clone(),
+            default_weights,
+            rest_properties: HashMap::new(),
+        };
+        debug_node_tree(model.root_nodes().into_iter());
+        Ok(model)
     }
 
     pub fn draw(
@@ -125,7 +250,8 @@ impl Model {
 
         struct Transforms<'a> {
             node: Vec<mat4<f32>>,
-            model: Vec<mat4<f32>>,
+            /// Node, it's glTF mesh and transform for every node, that has a mesh
+            instances: Vec<(usize, usize, mat4<f32>)>,
             camera: Vec<mat4<f32>>,
             camera_name: Option<&'a str>,
             camera_index: Option<usize>,
@@ -133,7 +259,7 @@ impl Model {
 
         let mut transforms = Transforms {
             node: vec![mat4::identity(); self.transforms.len()],
-            model: vec![mat4::identity(); self.meshes.len()],
+            instances: Vec::new(),
             camera: vec![mat4::identity(); self.cameras.len()],
             camera_name: camera,
             camera_index: None,
@@ -145,12 +271,15 @@ impl Model {
             model: &Model,
             transforms: &mut Transforms,
         ) {
-            transforms.node[node.index()] = mat4::new(node.transform().matrix())
-                * parent_transform
-                * model.transforms[node.index()];
+            transforms.node[node.index()] =
+                model.final_transform(node.index()).matrix() * parent_transform;
 
             if let Some(mesh) = node.mesh() {
-                transforms.model[mesh.index()] = transforms.node[node.index()];
+                transforms.instances.push((
+                    node.index(),
+                    mesh.index(),
+                    transforms.node[node.index()],
+                ));
             }
 
             if let Some(camera) = node.camera() {
@@ -165,7 +294,7 @@ impl Model {
             }
         }
 
-        for node in self.document.default_scene().unwrap().nodes() {
+        for node in self.root_nodes() {
             traverse(node, self.transform, self, &mut transforms);
         }
 
@@ -175,33 +304,34 @@ impl Model {
             }
         }
 
-        for (index, mesh) in self.meshes.iter().enumerate() {
-            ugli::draw(
-                framebuffer,
-                program,
-                mesh.mode,
-                &mesh.data,
-                (
+        for &(node, mesh, model_matrix) in &transforms.instances {
+            for mesh in &self.meshes[mesh] {
+                mesh.draw(
+                    framebuffer,
+                    program,
                     (
-                        if let Some(camera) = transforms.camera_index {
-                            vec![geng::camera::Uniforms3d {
-                                u_projection_matrix: self.cameras[camera]
-                                    .matrix(framebuffer.size().map(|x| x as f32)),
-                                u_view_matrix: transforms.camera[camera].transpose().inverse(),
-                            }]
-                        } else {
-                            Vec::new()
-                        },
-                        ugli::SingleUniform::new("u_model_matrix", transforms.model[index]),
+                        (
+                            if let Some(camera) = transforms.camera_index {
+                                vec![geng::camera::Uniforms3d {
+                                    u_projection_matrix: self.cameras[camera]
+                                        .matrix(framebuffer.size().map(|x| x as f32)),
+                                    u_view_matrix: transforms.camera[camera].transpose().inverse(),
+                                }]
+                            } else {
+                                Vec::new()
+                            },
+                            ugli::SingleUniform::new("u_model_matrix", model_matrix),
+                            morph_weights_uniforms(&self.weights[node]),
+                        ),
+                        (
+                            self.armature_uniforms(&transforms.node),
+                            self.materials[mesh.material].uniforms(),
+                            &uniforms,
+                        ),
                     ),
-                    (
-                        self.armature_uniforms(&transforms.node),
-                        self.materials[mesh.material].uniforms(),
-                        &uniforms,
-                    ),
-                ),
-                draw_parameters,
-            );
+                    draw_parameters,
+                );
+            }
         }
     }
 }
@@ -235,14 +365,84 @@ pub enum MeshLoadError {
     MissingIndices,
     #[error("Missing inverse bind matrices")]
     MissingInverseBindMatrices,
-    #[error("No default scene, multiple scenes are not supported yet")]
-    NoDefaultScene,
     #[error("Missing animation inputs (time)")]
     MissingAnimationInputs,
     #[error("Missing animation outputs")]
     MissingAnimationOutputs,
 }
 
+/// Animation, node or scene, referenced either by it's index or by it's name.
+/// Serialized as a plain number or string
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Id {
+    Index(usize),
+    Name(String),
+}
+
+impl From<usize> for Id {
+    fn from(value: usize) -> Self {
+        Self::Index(value)
+    }
+}
+
+impl From<&str> for Id {
+    fn from(value: &str) -> Self {
+        Self::Name(value.to_owned())
+    }
+}
+
+impl From<String> for Id {
+    fn from(value: String) -> Self {
+        Self::Name(value)
+    }
+}
+
+impl fmt::Display for Id {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Index(index) => write!(f, "#{}", index),
+            Self::Name(name) => write!(f, "{:?}", name),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum AnimationError {
+    #[error("Animation {0} not found")]
+    NotFound(Id),
+    #[error("Animation {0} is not additive")]
+    NotAdditive(Id),
+    #[error(transparent)]
+    NodeError(#[from] NodeError),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum NodeError {
+    #[error("Node {0} not found")]
+    NotFound(Id),
+    #[error("Node {0} has no parent")]
+    NoParent(Id),
+    #[error("Node {0} is not a descendant of node {1}")]
+    NotDescendant(Id, Id),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum BakeError {
+    #[error(transparent)]
+    AnimationError(#[from] AnimationError),
+    #[error("Skin {0} not found")]
+    SkinNotFound(Id),
+    #[error("Frame rate must be positive, got {0}")]
+    InvalidFrameRate(f32),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SceneError {
+    #[error("Scene {0} not found")]
+    NotFound(Id),
+}
+
 pub fn debug_node_tree<'a>(nodes: impl Iterator<Item = gltf::Node<'a>>) {
     fn traverse(node: gltf::Node, indent: usize) {
         println!(
@@ -0,0 +1,47 @@
+use super::*;
+
+/// Punctual light from the `KHR_lights_punctual` extension.
+/// Lights shine along the -Z axis of the nodes they are attached to
+#[derive(Debug, Clone, PartialEq)]
+pub struct Light {
+    pub name: Option<String>,
+    pub color: Rgba<f32>,
+    /// Candela for point and spot lights, lux for directional lights
+    pub intensity: f32,
+    /// Distance, at which the light's intensity reaches zero. [None] means infinite range
+    pub range: Option<f32>,
+    pub kind: LightKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    Directional,
+    Point,
+    Spot {
+        inner_cone_angle: f32,
+        outer_cone_angle: f32,
+    },
+}
+
+impl From<gltf::khr_lights_punctual::Light<'_>> for Light {
+    fn from(light: gltf::khr_lights_punctual::Light) -> Self {
+        let [r, g, b] = light.color();
+        Self {
+            name: light.name().map(str::to_owned),
+            color: Rgba::new(r, g, b, 1.0),
+            intensity: light.intensity(),
+            range: light.range(),
+            kind: match light.kind() {
+                gltf::khr_lights_punctual::Kind::Directional => LightKind::Directional,
+                gltf::khr_lights_punctual::Kind::Point => LightKind::Point,
+                gltf::khr_lights_punctual::Kind::Spot {
+                    inner_cone_angle,
+                    outer_cone_angle,
+                } => LightKind::Spot {
+                    inner_cone_angle,
+                    outer_cone_angle,
+                },
+            },
+        }
+    }
+}
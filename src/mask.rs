@@ -0,0 +1,176 @@
+use super::*;
+
+/// Per-node weights, that limit which nodes are affected by animations.
+/// Weight of 1 means the node is fully affected, 0 means it's not affected at all
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoneMask {
+    pub weights: Vec<f32>,
+}
+
+impl BoneMask {
+    /// Mask that doesn't include any node
+    pub fn empty(nodes: usize) -> Self {
+        Self {
+            weights: vec![0.0; nodes],
+        }
+    }
+
+    /// Mask that includes all nodes
+    pub fn full(nodes: usize) -> Self {
+        Self {
+            weights: vec![1.0; nodes],
+        }
+    }
+
+    /// Mask that includes all joints of the skin
+    pub fn from_skin(skin: &Skin, nodes: usize) -> Self {
+        let mut mask = Self::empty(nodes);
+        for joint in &skin.joints {
+            mask.weights[joint.node_index] = 1.0;
+        }
+        mask
+    }
+
+    /// Mask that includes the node and all of it's descendants
+    pub fn from_subtree(model: &Model, root: impl Into<Id>) -> Result<Self, NodeError> {
+        let mut mask = Self::empty(model.transforms.len());
+        mask.set_subtree(model, root, 1.0)?;
+        Ok(mask)
+    }
+
+    pub fn weight(&self, node: usize) -> f32 {
+        self.weights.get(node).copied().unwrap_or(0.0)
+    }
+
+    /// Sets weight of the node and all of it's descendants
+    pub fn set_subtree(
+        &mut self,
+        model: &Model,
+        root: impl Into<Id>,
+        weight: f32,
+    ) -> Result<(), NodeError> {
+        fn traverse(node: gltf::Node, weights: &mut [f32], weight: f32) {
+            weights[node.index()] = weight;
+            for child in node.children() {
+                traverse(child, weights, weight);
+            }
+        }
+
+        let root = model.node_index(root)?;
+        if let Some(node) = model.document.nodes().nth(root) {
+            traverse(node, &mut self.weights, weight);
+        }
+        Ok(())
+    }
+
+    /// Swaps included and excluded nodes
+    pub fn invert(&mut self) {
+        for weight in &mut self.weights {
+            *weight = 1.0 - *weight;
+        }
+    }
+}
+
+impl Pose {
+    /// Like [Pose::blend], but the weight of every node is multiplied by the mask
+    pub fn blend_masked(&mut self, other: &Pose, weight: f32, mask: &BoneMask) {
+        for (node, (transform, other)) in
+            std::iter::zip(&mut self.transforms, &other.transforms).enumerate()
+        {
+            *transform = transform.lerp(other, weight * mask.weight(node));
+        }
+        for (node, (weights, other)) in
+            std::iter::zip(&mut self.weights, &other.weights).enumerate()
+        {
+            let weight = weight * mask.weight(node);
+            for (value, other) in std::iter::zip(weights, other) {
+                *value += (other - *value) * weight;
+            }
+        }
+    }
+
+    /// Like [Pose::add], but the weight of every node is multiplied by the mask
+    pub fn add_masked(&mut self, additive: &Pose, reference: &Pose, weight: f32, mask: &BoneMask) {
+        for (node, ((transform, additive), reference)) in
+            std::iter::zip(&mut self.transforms, &additive.transforms)
+                .zip(&reference.transforms)
+                .enumerate()
+        {
+            *transform = transform.add(additive, reference, weight * mask.weight(node));
+        }
+        for (node, ((weights, additive), reference)) in
+            std::iter::zip(&mut self.weights, &additive.weights)
+                .zip(&reference.weights)
+                .enumerate()
+        {
+            let weight = weight * mask.weight(node);
+            for ((value, additive), reference) in std::iter::zip(weights, additive).zip(reference) {
+                *value += (additive - reference) * weight;
+            }
+        }
+    }
+}
+
+impl Model {
+    /// Mask that includes the node and all of it's descendants, e.g. "everything below Spine"
+    pub fn subtree_mask(&self, root: impl Into<Id>) -> Result<BoneMask, NodeError> {
+        BoneMask::from_subtree(self, root)
+    }
+
+    /// Like [Model::apply_animation], but only affects nodes included in the mask.
+    /// Animated properties don't belong to nodes, so they are not applied
+    pub fn apply_animation_masked(
+        &mut self,
+        id: impl Into<Id>,
+        time: f32,
+        mask: &BoneMask,
+    ) -> Result<(), AnimationError> {
+        let animation = self.animation(id)?;
+        let mut pose = self.pose();
+        let mut sampled = pose.clone();
+        animation.sample(time, &mut sampled);
+        pose.blend_masked(&sampled, 1.0, mask);
+        self.apply_pose(&pose);
+        Ok(())
+    }
+
+    /// Like [Model::apply_additive_animation], but only affects nodes included in the mask.
+    /// Animated properties are not applied
+    pub fn apply_additive_animation_masked(
+        &mut self,
+        id: impl Into<Id>,
+        time: f32,
+        weight: f32,
+        mask: &BoneMask,
+    ) -> Result<(), AnimationError> {
+        let index = self.animation_index(id)?;
+        let animation = &self.animations[index];
+        let Some(reference) = &animation.additive else {
+            return Err(AnimationError::NotAdditive(Id::Index(index)));
+        };
+
+        let mut additive = reference.clone();
+        animation.sample(time, &mut additive);
+        let mut pose = self.pose();
+        pose.add_masked(&additive, reference, weight, mask);
+        self.apply_pose(&pose);
+        Ok(())
+    }
+
+    /// Like [Model::blend_animations], but only affects nodes included in the mask.
+    /// Other nodes keep their current transforms, e.g. to blend upper body animations over locomotion.
+    /// Animated properties don't belong to nodes, so they are not applied
+    pub fn blend_animations_masked<A: Into<Id>>(
+        &mut self,
+        animations: impl IntoIterator<Item = (A, f32, f32)>,
+        mask: &BoneMask,
+    ) -> Result<(), AnimationError> {
+        let animations = self.animation_indices(animations)?;
+        if let Some(blended) = self.blended_pose(&animations) {
+            let mut pose = self.pose();
+            pose.blend_masked(&blended, 1.0, mask);
+            self.apply_pose(&pose);
+        }
+        Ok(())
+    }
+}
@@ -3,6 +3,47 @@ use super::*;
 pub struct Material {
     pub base_color: Rgba<f32>,
     pub base_texture: ugli::Texture,
+    /// UV transform of the base texture (`KHR_texture_transform`)
+    pub base_texture_transform: TextureTransform,
+    pub emissive_factor: vec3<f32>,
+    /// Multiplier of the emissive factor (`KHR_materials_emissive_strength`)
+    pub emissive_strength: f32,
+}
+
+/// Offset, rotation and scale of texture coordinates, as defined by `KHR_texture_transform`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureTransform {
+    pub offset: vec2<f32>,
+    /// Rotation of the UVs in radians, counter-clockwise
+    pub rotation: f32,
+    pub scale: vec2<f32>,
+}
+
+impl TextureTransform {
+    pub fn identity() -> Self {
+        Self {
+            offset: vec2::ZERO,
+            rotation: 0.0,
+            scale: vec2(1.0, 1.0),
+        }
+    }
+
+    /// Matrix, that transforms UVs: `(matrix * vec3(uv, 1.0)).xy`
+    pub fn matrix(&self) -> mat3<f32> {
+        let (sin, cos) = self.rotation.sin_cos();
+        let Self { offset, scale, .. } = *self;
+        mat3::new([
+            [scale.x * cos, scale.y * sin, offset.x],
+            [-scale.x * sin, scale.y * cos, offset.y],
+            [0.0, 0.0, 1.0],
+        ])
+    }
+}
+
+impl Default for TextureTransform {
+    fn default() -> Self {
+        Self::identity()
+    }
 }
 
 impl Material {
@@ -26,11 +67,20 @@ impl Material {
                 || white_texture(ugli),
                 |texture| ugli_texture(ugli, texture, images),
             );
+        let base_texture_transform = material
+            .pbr_metallic_roughness()
+            .base_color_texture()
+            .and_then(|texture| texture.texture_transform())
+            .map_or_else(TextureTransform::identity, |transform| TextureTransform {
+                offset: vec2(transform.offset()[0], transform.offset()[1]),
+                rotation: transform.rotation(),
+                scale: vec2(transform.scale()[0], transform.scale()[1]),
+            });
+        let [r, g, b] = material.emissive_factor();
         // material.alpha_cutoff()
         // material.alpha_mode()
         // material.double_sided()
 
-        // material.emissive_factor()
         // material.emissive_texture()
         // material.normal_texture()
         // material.occlusion_texture()
@@ -38,6 +88,9 @@ impl Material {
         Ok(Self {
             base_color,
             base_texture,
+            base_texture_transform,
+            emissive_factor: vec3(r, g, b),
+            emissive_strength: material.emissive_strength().unwrap_or(1.0),
         })
     }
 
@@ -45,6 +98,8 @@ impl Material {
         ugli::uniforms! {
             u_base_color: self.base_color,
             u_base_texture: &self.base_texture,
+            u_base_texture_transform: self.base_texture_transform.matrix(),
+            u_emissive: self.emissive_factor * self.emissive_strength,
         }
     }
 }
@@ -11,10 +11,44 @@ pub struct Vertex {
     pub a_weights: [f32; 4],
 }
 
+/// [Vertex] with deltas of the first [MAX_MORPH_TARGETS] morph targets,
+/// only used by primitives, that have morph targets
+#[derive(Clone, Debug, PartialEq, ugli::Vertex)]
+pub struct MorphVertex {
+    pub a_pos: vec3<f32>,
+    pub a_normal: vec3<f32>,
+    pub a_uv: vec2<f32>,
+    pub a_color: Rgba<f32>,
+
+    pub a_joints: [f32; 4],
+    pub a_weights: [f32; 4],
+
+    pub a_morph_pos0: vec3<f32>,
+    pub a_morph_pos1: vec3<f32>,
+    pub a_morph_pos2: vec3<f32>,
+    pub a_morph_pos3: vec3<f32>,
+    pub a_morph_normal0: vec3<f32>,
+    pub a_morph_normal1: vec3<f32>,
+    pub a_morph_normal2: vec3<f32>,
+    pub a_morph_normal3: vec3<f32>,
+}
+
+/// Maximum number of morph targets, that can be applied in the shader (see `morph_pos()` in the prelude)
+pub const MAX_MORPH_TARGETS: usize = 4;
+
+/// Vertex buffer of a primitive. Morph target deltas are only stored for primitives, that have morph targets,
+/// other ones leave `a_morph_*` attributes unbound, so they read as zeros in the shader
+pub enum MeshData {
+    Static(ugli::VertexBuffer<Vertex>),
+    Morphed(ugli::VertexBuffer<MorphVertex>),
+}
+
 pub struct Mesh {
-    pub data: ugli::VertexBuffer<Vertex>,
+    pub data: MeshData,
     pub material: usize,
     pub mode: ugli::DrawMode,
+    /// Index of the glTF mesh this primitive belongs to
+    pub mesh_index: usize,
 }
 
 impl Mesh {
@@ -23,6 +57,7 @@ impl Mesh {
         primitive: gltf::Primitive,
         buffers: &[gltf::buffer::Data],
         material: usize,
+        mesh_index: usize,
     ) -> Result<Self, MeshLoadError> {
         let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|x| &**x));
 
@@ -65,12 +100,29 @@ impl Mesh {
             .read_weights(0)
             .map(|weights| weights.into_f32().collect_vec());
 
+        // Morph targets, tangent deltas are not used by the prelude
+        fn read_vec3(values: Option<impl Iterator<Item = [f32; 3]>>) -> Option<Vec<vec3<f32>>> {
+            values.map(|values| values.map(|[x, y, z]| vec3(x, y, z)).collect_vec())
+        }
+        let morph_targets = reader
+            .read_morph_targets()
+            .map(|(positions, normals, _tangents)| (read_vec3(positions), read_vec3(normals)))
+            .collect_vec();
+        if morph_targets.len() > MAX_MORPH_TARGETS {
+            log::warn!(
+                "Primitive has {} morph targets, only the first {} will be rendered",
+                morph_targets.len(),
+                MAX_MORPH_TARGETS
+            );
+        }
+
         // * Other
         let indices = reader
             .read_indices()
             .ok_or(MeshLoadError::MissingIndices)?
             .into_u32()
-            .map(|x| x as usize);
+            .map(|x| x as usize)
+            .collect_vec();
 
         let mode = match primitive.mode() {
             gltf::mesh::Mode::Points => ugli::DrawMode::Points,
@@ -83,30 +135,117 @@ impl Mesh {
         };
 
         // * VBO
-        let data = ugli::VertexBuffer::new_static(
-            ugli,
-            indices
-                .map(|index| Vertex {
-                    a_pos: positions[index],
-                    a_normal: normals
-                        .as_ref()
-                        .map_or(vec3::ZERO, |normals| normals[index]),
-
-                    a_uv: uvs.as_ref().map_or(vec2::ZERO, |uvs| uvs[index]),
-                    a_color: colors.as_ref().map_or(Rgba::WHITE, |colors| colors[index]),
-
-                    a_joints: joints.as_ref().map_or([0.0; 4], |joints| joints[index]),
-                    a_weights: weights.as_ref().map_or([0.0; 4], |weights| weights[index]),
-                })
-                .collect(),
-        );
+        let vertices = indices.iter().map(|&index| Vertex {
+            a_pos: positions[index],
+            a_normal: normals
+                .as_ref()
+                .map_or(vec3::ZERO, |normals| normals[index]),
+
+            a_uv: uvs.as_ref().map_or(vec2::ZERO, |uvs| uvs[index]),
+            a_color: colors.as_ref().map_or(Rgba::WHITE, |colors| colors[index]),
+
+            a_joints: joints.as_ref().map_or([0.0; 4], |joints| joints[index]),
+            a_weights: weights.as_ref().map_or([0.0; 4], |weights| weights[index]),
+        });
+
+        let data = if morph_targets.is_empty() {
+            MeshData::Static(ugli::VertexBuffer::new_static(ugli, vertices.collect()))
+        } else {
+            let delta = |target: usize, normal: bool, index: usize| {
+                let Some((positions, normals)) = morph_targets.get(target) else {
+                    return vec3::ZERO;
+                };
+                let deltas = if normal { normals } else { positions };
+                deltas.as_ref().map_or(vec3::ZERO, |deltas| deltas[index])
+            };
+            MeshData::Morphed(ugli::VertexBuffer::new_static(
+                ugli,
+                std::iter::zip(&indices, vertices)
+                    .map(|(&index, vertex)| MorphVertex {
+                        a_pos: vertex.a_pos,
+                        a_normal: vertex.a_normal,
+                        a_uv: vertex.a_uv,
+                        a_color: vertex.a_color,
+                        a_joints: vertex.a_joints,
+                        a_weights: vertex.a_weights,
+
+                        a_morph_pos0: delta(0, false, index),
+                        a_morph_pos1: delta(1, false, index),
+                        a_morph_pos2: delta(2, false, index),
+                        a_morph_pos3: delta(3, false, index),
+                        a_morph_normal0: delta(0, true, index),
+                        a_morph_normal1: delta(1, true, index),
+                        a_morph_normal2: delta(2, true, index),
+                        a_morph_normal3: delta(3, true, index),
+                    })
+                    .collect(),
+            ))
+        };
 
         Ok(Self {
             data,
             material,
             mode,
+            mesh_index,
         })
     }
+
+    /// Draws the primitive with whichever vertex buffer it has
+    pub fn draw(
+        &self,
+        framebuffer: &mut ugli::Framebuffer,
+        program: &ugli::Program,
+        uniforms: impl ugli::Uniforms,
+        draw_parameters: &ugli::DrawParameters,
+    ) {
+        match &self.data {
+            MeshData::Static(data) => ugli::draw(
+                framebuffer,
+                program,
+                self.mode,
+                data,
+                uniforms,
+                draw_parameters,
+            ),
+            MeshData::Morphed(data) => ugli::draw(
+                framebuffer,
+                program,
+                self.mode,
+                data,
+                uniforms,
+                draw_parameters,
+            ),
+        }
+    }
+
+    /// Like [Mesh::draw], but draws every instance
+    pub fn draw_instanced<I: ugli::Vertex>(
+        &self,
+        framebuffer: &mut ugli::Framebuffer,
+        program: &ugli::Program,
+        instances: &ugli::VertexBuffer<I>,
+        uniforms: impl ugli::Uniforms,
+        draw_parameters: &ugli::DrawParameters,
+    ) {
+        match &self.data {
+            MeshData::Static(data) => ugli::draw(
+                framebuffer,
+                program,
+                self.mode,
+                ugli::instanced(data, instances),
+                uniforms,
+                draw_parameters,
+            ),
+            MeshData::Morphed(data) => ugli::draw(
+                framebuffer,
+                program,
+                self.mode,
+                ugli::instanced(data, instances),
+                uniforms,
+                draw_parameters,
+            ),
+        }
+    }
 }
 
 impl Debug for Mesh {
@@ -114,6 +253,16 @@ impl Debug for Mesh {
         f.debug_struct("Mesh")
             .field("material", &self.material)
             .field("mode", &self.mode)
+            .field("mesh_index", &self.mesh_index)
+            .field("morphed", &matches!(self.data, MeshData::Morphed(_)))
             .finish()
     }
 }
+
+/// Uniforms with morph target weights for the shader prelude
+pub fn morph_weights_uniforms(weights: &[f32]) -> impl ugli::Uniforms {
+    let weight = |index: usize| weights.get(index).copied().unwrap_or(0.0);
+    ugli::uniforms! {
+        u_morph_weights: vec4(weight(0), weight(1), weight(2), weight(3)),
+    }
+}
@@ -0,0 +1,345 @@
+use super::*;
+
+/// Axis, that is flipped by mirroring, i.e. the normal of the mirror plane
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MirrorAxis {
+    /// Mirror left and right (across the YZ plane), which is what glTF exporters use for X
+    #[default]
+    X,
+    Y,
+    Z,
+}
+
+impl MirrorAxis {
+    fn reflect(&self, v: vec3<f32>) -> vec3<f32> {
+        match self {
+            Self::X => vec3(-v.x, v.y, v.z),
+            Self::Y => vec3(v.x, -v.y, v.z),
+            Self::Z => vec3(v.x, v.y, -v.z),
+        }
+    }
+
+    /// Rotation as seen in the mirror: the axis is reflected and the angle is reversed
+    fn reflect_rotation(&self, q: vec4<f32>) -> vec4<f32> {
+        let axis = -self.reflect(vec3(q.x, q.y, q.z));
+        vec4(axis.x, axis.y, axis.z, q.w)
+    }
+}
+
+/// Suffixes (and infixes), that mark left and right nodes
+const MIRROR_NAME_PAIRS: [(&str, &str); 6] = [
+    (".L", ".R"),
+    ("_L", "_R"),
+    (".l", ".r"),
+    ("_l", "_r"),
+    ("Left", "Right"),
+    ("left", "right"),
+];
+
+/// Name of the node on the other side, following common naming conventions
+/// (`Arm.L`/`Arm.R`, `hand_l`/`hand_r`, `LeftFoot`/`RightFoot`)
+pub fn mirrored_name(name: &str) -> Option<String> {
+    for (left, right) in MIRROR_NAME_PAIRS {
+        let is_suffix = left.len() == 2;
+        for (from, to) in [(left, right), (right, left)] {
+            if is_suffix {
+                if let Some(base) = name.strip_suffix(from) {
+                    return Some(format!("{}{}", base, to));
+                }
+            } else if name.contains(from) {
+                return Some(name.replacen(from, to, 1));
+            }
+        }
+    }
+    None
+}
+
+/// Which node mirrors which, and across which plane
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mirror {
+    pub axis: MirrorAxis,
+    /// Mirrored counterpart of every node, unpaired nodes (like the spine) are paired with themselves
+    pub pairs: Vec<usize>,
+}
+
+impl Mirror {
+    /// Pairs nodes of the skeleton by names. `name_map` has explicit pairs (in any direction),
+    /// other nodes are paired with [mirrored_name]
+    pub fn from_names(
+        skeleton: &Skeleton,
+        axis: MirrorAxis,
+        name_map: &HashMap<String, String>,
+    ) -> Self {
+        let pairs = skeleton
+            .names
+            .iter()
+            .enumerate()
+            .map(|(node, name)| {
+                let Some(name) = name else {
+                    return node;
+                };
+                let mirrored = name_map
+                    .get(name)
+                    .cloned()
+                    .or_else(|| {
+                        name_map
+                            .iter()
+                            .find(|(_, other)| *other == name)
+                            .map(|(other, _)| other.clone())
+                    })
+                    .or_else(|| mirrored_name(name));
+                mirrored
+                    .and_then(|name| skeleton.node_index(&name))
+                    .unwrap_or(node)
+            })
+            .collect();
+        Self { axis, pairs }
+    }
+
+    /// Mirrors the local transform of the `node`, so that it can be used for it's pair.
+    /// The difference between rest transforms of the pair is kept, so rigs with
+    /// not exactly symmetrical bone orientations still get their rest pose mirrored into the rest pose
+    pub fn transform(
+        &self,
+        node: usize,
+        transform: &Transform,
+        rest_transforms: &[Transform],
+    ) -> Transform {
+        Transform {
+            translation: self.translation(node, transform.translation, rest_transforms),
+            rotation: quat_mul(
+                self.axis.reflect_rotation(transform.rotation),
+                self.rotation_correction(node, rest_transforms),
+            ),
+            scale: self.scale(node, transform.scale, rest_transforms),
+        }
+    }
+
+    fn translation(
+        &self,
+        node: usize,
+        translation: vec3<f32>,
+        rest_transforms: &[Transform],
+    ) -> vec3<f32> {
+        let pair = self.pairs[node];
+        self.axis.reflect(translation) - self.axis.reflect(rest_transforms[node].translation)
+            + rest_transforms[pair].translation
+    }
+
+    fn scale(&self, node: usize, scale: vec3<f32>, rest_transforms: &[Transform]) -> vec3<f32> {
+        let ratio = |axis: fn(vec3<f32>) -> f32| {
+            let (source, target) = (
+                axis(rest_transforms[node].scale),
+                axis(rest_transforms[self.pairs[node]].scale),
+            );
+            if source != 0.0 {
+                target / source
+            } else {
+                1.0
+            }
+        };
+        vec3(
+            scale.x * ratio(|v| v.x),
+            scale.y * ratio(|v| v.y),
+            scale.z * ratio(|v| v.z),
+        )
+    }
+
+    /// Rotation in the pair's local space, that turns the mirrored rest rotation of the node into the pair's one
+    fn rotation_correction(&self, node: usize, rest_transforms: &[Transform]) -> vec4<f32> {
+        let pair = self.pairs[node];
+        quat_mul(
+            quat_conjugate(self.axis.reflect_rotation(rest_transforms[node].rotation)),
+            rest_transforms[pair].rotation,
+        )
+    }
+}
+
+impl Pose {
+    /// Mirrored pose, see [Mirror::transform]. Morph target weights are kept as is
+    pub fn mirror(&self, mirror: &Mirror, rest_transforms: &[Transform]) -> Pose {
+        let mut pose = self.clone();
+        for (node, transform) in self.transforms.iter().enumerate() {
+            pose.transforms[mirror.pairs[node]] =
+                mirror.transform(node, transform, rest_transforms);
+        }
+        pose
+    }
+}
+
+impl Animation {
+    /// Mirrored animation: channels of paired nodes are swapped and their values are reflected,
+    /// see [Mirror::transform]. Morph target weights and animated properties are kept as is
+    pub fn mirror(&self, mirror: &Mirror, skeleton: &Skeleton) -> Animation {
+        let rest_transforms = &skeleton.rest_transforms;
+        let channels = self
+            .channels
+            .iter()
+            .map(|channel| {
+                // Properties don't belong to nodes, so they are kept as is
+                if let Outputs::Property { .. } = channel.outputs {
+                    return channel.clone();
+                }
+                let node = channel.target;
+                let pair = mirror.pairs[node];
+                // Cubic spline tangents are derivatives, so they are only reflected and not offset
+                let is_tangent = |index: usize| {
+                    channel.interpolation == Interpolation::CubicSpline && index % 3 != 1
+                };
+                let correction = mirror.rotation_correction(node, rest_transforms);
+                let rotation =
+                    |rotation| quat_mul(mirror.axis.reflect_rotation(rotation), correction);
+
+                let outputs = match &channel.outputs {
+                    Outputs::Translation(translations) => Outputs::Translation(
+                        translations
+                            .iter()
+                            .enumerate()
+                            .map(|(index, &translation)| {
+                                if is_tangent(index) {
+                                    mirror.axis.reflect(translation)
+                                } else {
+                                    mirror.translation(node, translation, rest_transforms)
+                                }
+                            })
+                            .collect(),
+                    ),
+                    Outputs::Rotation(rotations) => {
+                        Outputs::Rotation(rotations.iter().copied().map(rotation).collect())
+                    }
+                    Outputs::QuantizedRotation(rotations) => Outputs::QuantizedRotation(
+                        rotations
+                            .iter()
+                            .map(|&value| quantize_rotation(rotation(dequantize_rotation(value))))
+                            .collect(),
+                    ),
+                    Outputs::Scale(scales) => Outputs::Scale(
+                        scales
+                            .iter()
+                            .map(|&scale| mirror.scale(node, scale, rest_transforms))
+                            .collect(),
+                    ),
+                    Outputs::Weights(_) | Outputs::Property { .. } => channel.outputs.clone(),
+                };
+
+                let target = match channel.outputs {
+                    // Morph target weights move to the paired mesh, if there is one
+                    Outputs::Weights(_) if skeleton.meshes[pair].is_none() => node,
+                    _ => pair,
+                };
+                Channel {
+                    target,
+                    interpolation: channel.interpolation,
+                    inputs: channel.inputs.clone(),
+                    outputs,
+                }
+            })
+            .collect();
+
+        Animation {
+            name: self.name.as_ref().map(|name| format!("{}_mirrored", name)),
+            channels,
+            events: self.events.clone(),
+            clips: self.clips.clone(),
+            additive: self
+                .additive
+                .as_ref()
+                .map(|pose| pose.mirror(mirror, rest_transforms)),
+        }
+    }
+}
+
+impl Model {
+    /// Pairs nodes of the model by names, see [Mirror::from_names]
+    pub fn mirror(&self, axis: MirrorAxis, name_map: &HashMap<String, String>) -> Mirror {
+        Mirror::from_names(&self.skeleton(), axis, name_map)
+    }
+
+    /// Mirrored copy of an animation, see [Animation::mirror].
+    /// It is named `<name>_mirrored` and can be added with [Model::add_animation]
+    pub fn mirror_animation(
+        &self,
+        id: impl Into<Id>,
+        mirror: &Mirror,
+    ) -> Result<Animation, AnimationError> {
+        Ok(self.animation(id)?.mirror(mirror, &self.skeleton()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert_eq!(mirrored_name("Arm.L").as_deref(), Some("Arm.R"));
+        assert_eq!(mirrored_name("hand_r").as_deref(), Some("hand_l"));
+        assert_eq!(mirrored_name("LeftFoot").as_deref(), Some("RightFoot"));
+        assert_eq!(mirrored_name("Spine"), None);
+    }
+
+    fn assert_close(a: &Transform, b: &Transform) {
+        assert!(
+            (a.translation - b.translation).len() < 1e-5,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+        assert!((a.scale - b.scale).len() < 1e-5, "{:?} != {:?}", a, b);
+        assert!(
+            vec4::dot(a.rotation, b.rotation).abs() > 1.0 - 1e-5,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    /// Left and right shoulders with mirrored rest transforms
+    fn shoulders() -> (Mirror, Vec<Transform>) {
+        let left = Transform {
+            translation: vec3(1.0, 2.0, 0.0),
+            rotation: quat_from_axis_angle(vec3(0.0, 0.0, 1.0), 0.5),
+            scale: vec3(1.0, 1.0, 1.0),
+        };
+        let right = Transform {
+            translation: vec3(-1.0, 2.0, 0.0),
+            rotation: quat_from_axis_angle(vec3(0.0, 0.0, 1.0), -0.5),
+            ..left
+        };
+        let mirror = Mirror {
+            axis: MirrorAxis::X,
+            pairs: vec![1, 0],
+        };
+        (mirror, vec![left, right])
+    }
+
+    #[test]
+    fn rest_pose_mirrors_into_itself() {
+        let (mirror, rest_transforms) = shoulders();
+        let pose = Pose::new(rest_transforms.clone(), vec![Vec::new(); 2]);
+        let mirrored = pose.mirror(&mirror, &rest_transforms);
+        for (a, b) in std::iter::zip(&mirrored.transforms, &rest_transforms) {
+            assert_close(a, b);
+        }
+    }
+
+    #[test]
+    fn mirroring_twice_restores_the_pose() {
+        let (mirror, rest_transforms) = shoulders();
+        let raised = Transform {
+            translation: vec3(1.5, 2.5, 0.5),
+            rotation: normalize_quat(vec4(0.3, -0.2, 0.4, 0.8)),
+            scale: vec3(1.0, 1.2, 1.0),
+        };
+        let pose = Pose::new(vec![raised, rest_transforms[1]], vec![Vec::new(); 2]);
+        let mirrored = pose.mirror(&mirror, &rest_transforms);
+        // The raised arm moved to the other side
+        assert_close(&mirrored.transforms[0], &rest_transforms[0]);
+        assert_eq!(mirrored.transforms[1].translation.x, -1.5);
+
+        let restored = mirrored.mirror(&mirror, &rest_transforms);
+        for (a, b) in std::iter::zip(&restored.transforms, &pose.transforms) {
+            assert_close(a, b);
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use super::*;
+
+impl Model {
+    /// Finds index of a node in [Model::transforms]
+    pub fn node_index(&self, id: impl Into<Id>) -> Result<usize, NodeError> {
+        let id = id.into();
+        let index = match &id {
+            Id::Index(index) => Some(*index).filter(|&index| index < self.transforms.len()),
+            Id::Name(name) => find_node(self.document.nodes().map(|node| node.name()), name),
+        };
+        index.ok_or(NodeError::NotFound(id))
+    }
+
+    /// Index of the parent of every node, [None] for root nodes
+    pub fn parents(&self) -> Vec<Option<usize>> {
+        let mut parents = vec![None; self.transforms.len()];
+        for node in self.document.nodes() {
+            for child in node.children() {
+                parents[child.index()] = Some(node.index());
+            }
+        }
+        parents
+    }
+
+    /// Current transform of the node relative to the scene root, with overrides applied ([Model::transform] is not applied)
+    pub fn model_space_transform(&self, node: impl Into<Id>) -> Result<Transform, NodeError> {
+        let node = self.node_index(node)?;
+        Ok(model_space_transform(self, &self.parents(), node))
+    }
+}
+
+/// Transform of the node relative to the scene root, given `parents` of all nodes.
+/// Overrides are applied, so IK works on the transforms, that are actually drawn
+pub(crate) fn model_space_transform(
+    model: &Model,
+    parents: &[Option<usize>],
+    node: usize,
+) -> Transform {
+    let local = model.final_transform(node);
+    match parents[node] {
+        Some(parent) => model_space_transform(model, parents, parent).compose(&local),
+        None => local,
+    }
+}
+
+/// Index of the first node with the given name, `names` are names of all nodes in order
+pub(crate) fn find_node<'a>(
+    names: impl IntoIterator<Item = Option<&'a str>>,
+    name: &str,
+) -> Option<usize> {
+    names.into_iter().position(|node| node == Some(name))
+}
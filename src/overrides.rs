@@ -0,0 +1,120 @@
+use super::*;
+
+/// Procedural change of a node's local transform, combined with the animated transform when drawing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransformOverride {
+    /// Replaces components of the animated transform, [None] components keep the animated value
+    Absolute {
+        translation: Option<vec3<f32>>,
+        rotation: Option<vec4<f32>>,
+        scale: Option<vec3<f32>>,
+    },
+    /// Applied on top of the animated transform: translation is added, rotation and scale are multiplied
+    Additive(Transform),
+}
+
+impl TransformOverride {
+    /// Replaces the whole animated transform
+    pub fn transform(transform: Transform) -> Self {
+        Self::Absolute {
+            translation: Some(transform.translation),
+            rotation: Some(transform.rotation),
+            scale: Some(transform.scale),
+        }
+    }
+
+    /// Replaces only the animated translation
+    pub fn translation(translation: vec3<f32>) -> Self {
+        Self::Absolute {
+            translation: Some(translation),
+            rotation: None,
+            scale: None,
+        }
+    }
+
+    /// Replaces only the animated rotation
+    pub fn rotation(rotation: vec4<f32>) -> Self {
+        Self::Absolute {
+            translation: None,
+            rotation: Some(rotation),
+            scale: None,
+        }
+    }
+
+    /// Replaces only the animated scale
+    pub fn scale(scale: vec3<f32>) -> Self {
+        Self::Absolute {
+            translation: None,
+            rotation: None,
+            scale: Some(scale),
+        }
+    }
+
+    pub fn apply(&self, transform: &Transform) -> Transform {
+        match *self {
+            Self::Absolute {
+                translation,
+                rotation,
+                scale,
+            } => Transform {
+                translation: translation.unwrap_or(transform.translation),
+                rotation: rotation.unwrap_or(transform.rotation),
+                scale: scale.unwrap_or(transform.scale),
+            },
+            Self::Additive(additive) => transform.add(&additive, &Transform::identity(), 1.0),
+        }
+    }
+}
+
+impl Model {
+    /// Sets an override of the node, replacing the previous one.
+    /// Overrides are kept until cleared, even when [Model::reset_transforms] is called
+    pub fn set_override(
+        &mut self,
+        node: impl Into<Id>,
+        value: TransformOverride,
+    ) -> Result<(), NodeError> {
+        let node = self.node_index(node)?;
+        self.overrides[node] = Some(value);
+        Ok(())
+    }
+
+    /// Removes the override of the node, returning it
+    pub fn clear_override(
+        &mut self,
+        node: impl Into<Id>,
+    ) -> Result<Option<TransformOverride>, NodeError> {
+        let node = self.node_index(node)?;
+        Ok(self.overrides[node].take())
+    }
+
+    pub fn clear_overrides(&mut self) {
+        self.overrides.fill(None);
+    }
+
+    /// Local transform of the node, that is used for drawing: animated transform with the override applied
+    pub fn final_transform(&self, node: usize) -> Transform {
+        overridden(&self.transforms[node], &self.overrides[node])
+    }
+
+    /// Current pose with overrides applied to the transforms, as it is drawn
+    pub fn final_pose(&self) -> Pose {
+        Pose::new(self.pose().final_transforms(self), self.weights.clone())
+    }
+}
+
+impl Pose {
+    /// Local transforms of the pose with overrides of the model applied, see [Model::final_transform]
+    pub fn final_transforms(&self, model: &Model) -> Vec<Transform> {
+        std::iter::zip(&self.transforms, &model.overrides)
+            .map(|(transform, value)| overridden(transform, value))
+            .collect()
+    }
+}
+
+fn overridden(transform: &Transform, value: &Option<TransformOverride>) -> Transform {
+    match value {
+        Some(value) => value.apply(transform),
+        None => *transform,
+    }
+}
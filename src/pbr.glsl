@@ -15,9 +15,15 @@ void pbr() {
 #ifdef FRAGMENT_SHADER
 uniform vec4 u_base_color;
 uniform sampler2D u_base_texture;
+uniform mat3 u_base_texture_transform;
+uniform vec3 u_emissive;
 
 vec4 sample_material_texture(vec2 uv) {
-    return texture2D(u_base_texture, uv) * u_base_color;
+    return texture2D(u_base_texture, (u_base_texture_transform * vec3(uv, 1.0)).xy) * u_base_color;
+}
+
+vec3 material_emissive() {
+    return u_emissive;
 }
 
 // uniform sampler2D u_base_color_texture;
@@ -0,0 +1,399 @@
+use super::*;
+
+/// What [AnimationPlayer] does, when it reaches the end of the animation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoopMode {
+    /// Play once, then rewind to the first frame and stop
+    Once,
+    /// Start over from the beginning
+    #[default]
+    Loop,
+    /// Play backwards to the beginning, then forwards again and so on
+    PingPong,
+    /// Play once and hold the last frame
+    ClampForever,
+}
+
+/// Playback state of a single animation
+#[derive(Debug, Clone)]
+pub struct AnimationPlayer {
+    pub speed: f32,
+    pub loop_mode: LoopMode,
+    pub paused: bool,
+
+    /// Index of the animation in [Model::animations].
+    /// Private, so that it can't get out of sync with the cached duration
+    animation: usize,
+    duration: f32,
+    /// Time since the start of playback. With [LoopMode::Loop] and [LoopMode::PingPong] it's wrapped
+    /// into a single period, so it doesn't lose precision when playing for a long time
+    elapsed: f32,
+    /// How many periods were wrapped out of `elapsed`
+    loops: i64,
+    /// Playback time at the start and at the end of the last update, not wrapped.
+    /// The end is where playback stopped, even if [LoopMode::Once] then rewound to the start
+    pub(crate) step: (f32, f32),
+    finished: bool,
+    /// Playback is mostly monotonic, so keyframes are searched starting from the previous ones
+    cursor: AnimationCursor,
+}
+
+impl AnimationPlayer {
+    pub fn new(model: &Model, animation: impl Into<Id>) -> Result<Self, AnimationError> {
+        let animation = model.animation_index(animation)?;
+        Ok(Self {
+            speed: 1.0,
+            loop_mode: LoopMode::default(),
+            paused: false,
+
+            animation,
+            duration: model.animations[animation].duration(),
+            elapsed: 0.0,
+            loops: 0,
+            step: (0.0, 0.0),
+            finished: false,
+            cursor: AnimationCursor::new(),
+        })
+    }
+
+    pub fn with_loop_mode(self, loop_mode: LoopMode) -> Self {
+        Self { loop_mode, ..self }
+    }
+
+    pub fn with_speed(self, speed: f32) -> Self {
+        Self { speed, ..self }
+    }
+
+    /// Index of the played animation in [Model::animations]
+    pub fn animation(&self) -> usize {
+        self.animation
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    /// Current time inside the animation
+    pub fn time(&self) -> f32 {
+        self.time_at(self.elapsed)
+    }
+
+    /// Time inside the animation after `elapsed` seconds of playback
+    pub(crate) fn time_at(&self, elapsed: f32) -> f32 {
+        if self.duration <= 0.0 {
+            return 0.0;
+        }
+
+        match self.loop_mode {
+            LoopMode::Once | LoopMode::ClampForever => elapsed.clamp(0.0, self.duration),
+            LoopMode::Loop => elapsed.rem_euclid(self.duration),
+            LoopMode::PingPong => {
+                let time = elapsed.rem_euclid(self.duration * 2.0);
+                if time > self.duration {
+                    self.duration * 2.0 - time
+                } else {
+                    time
+                }
+            }
+        }
+    }
+
+    /// How many times a looping animation started over (a back and forth cycle counts once with [LoopMode::PingPong]).
+    /// Negative, if it went past the beginning while playing backwards
+    pub fn loops(&self) -> i64 {
+        self.loops
+    }
+
+    /// Returns true, if the animation reached it's end (with [LoopMode::Once] or [LoopMode::ClampForever])
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn play(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Jumps to the given time, restarting playback if it was finished
+    pub fn seek(&mut self, time: f32) {
+        self.elapsed = time;
+        self.loops = 0;
+        self.wrap();
+        self.step = (self.elapsed, self.elapsed);
+        self.finished = false;
+    }
+
+    pub fn restart(&mut self) {
+        self.seek(0.0);
+        self.play();
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        self.step = (self.elapsed, self.elapsed);
+        if self.paused || self.finished {
+            return;
+        }
+
+        self.elapsed += delta_time * self.speed;
+        self.step.1 = self.elapsed;
+        let out_of_range = self.elapsed >= self.duration && self.speed >= 0.0
+            || self.elapsed <= 0.0 && self.speed < 0.0;
+        match self.loop_mode {
+            LoopMode::Loop | LoopMode::PingPong => self.wrap(),
+            LoopMode::Once if out_of_range => {
+                self.step.1 = self.elapsed.clamp(0.0, self.duration);
+                self.elapsed = 0.0;
+                self.finished = true;
+            }
+            LoopMode::ClampForever if out_of_range => {
+                self.elapsed = self.elapsed.clamp(0.0, self.duration);
+                self.step.1 = self.elapsed;
+                self.finished = true;
+            }
+            LoopMode::Once | LoopMode::ClampForever => {}
+        }
+    }
+
+    /// Wraps `elapsed` into a single period of a looping animation
+    fn wrap(&mut self) {
+        let period = match self.loop_mode {
+            LoopMode::Loop => self.duration,
+            LoopMode::PingPong => self.duration * 2.0,
+            LoopMode::Once | LoopMode::ClampForever => return,
+        };
+        if period <= 0.0 {
+            return;
+        }
+        let periods = (self.elapsed / period).floor();
+        self.loops += periods as i64;
+        self.elapsed -= periods * period;
+    }
+
+    /// Events of the animation, that were crossed during the last [AnimationPlayer::update], in playback order.
+    /// Takes looping into account, so events are reported on every loop (and even several times per update,
+    /// if it's long enough)
+    pub fn crossed_events<'a>(&self, model: &'a Model) -> Vec<&'a AnimationEvent> {
+        match model.animations.get(self.animation) {
+            Some(animation) => self.crossed(&animation.events),
+            None => Vec::new(),
+        }
+    }
+
+    /// Which of the `events` were crossed during the last update.
+    /// Every update covers the time from where playback was up to (but not including) where it got,
+    /// so an event exactly at the boundary of two updates is reported once, by the later one.
+    /// This way events at the start fire when playback starts, and events at the end of a loop
+    /// fire together with the start of the next one. Only when playback finishes,
+    /// the time it stopped at is included, so that events at the end still fire
+    fn crossed<'a>(&self, events: &'a [AnimationEvent]) -> Vec<&'a AnimationEvent> {
+        let (from, to) = self.step;
+        if from == to || self.duration <= 0.0 {
+            return Vec::new();
+        }
+
+        // Event positions on the unwrapped timeline repeat every period
+        let duration = self.duration;
+        let (period, repeats) = match self.loop_mode {
+            LoopMode::Once | LoopMode::ClampForever => (duration, false),
+            LoopMode::Loop => (duration, true),
+            LoopMode::PingPong => (duration * 2.0, true),
+        };
+        let periods = if repeats {
+            let first = (from.min(to) / period).floor() as i64;
+            let last = (from.max(to) / period).floor() as i64;
+            first..=last
+        } else {
+            0..=0
+        };
+
+        let mut crossed = Vec::new();
+        for period_index in periods {
+            let offset = period_index as f32 * period;
+            for event in events {
+                let mut positions = vec![offset + event.time];
+                // Events at the ends are only crossed once, when playback turns around
+                if self.loop_mode == LoopMode::PingPong && 0.0 < event.time && event.time < duration
+                {
+                    positions.push(offset + period - event.time);
+                }
+                for position in positions {
+                    let inside = if to > from {
+                        from <= position && position < to
+                    } else {
+                        to < position && position <= from
+                    };
+                    if inside || self.finished && position == to {
+                        crossed.push((position, event));
+                    }
+                }
+            }
+        }
+
+        crossed.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        if to < from {
+            crossed.reverse();
+        }
+        crossed.into_iter().map(|(_, event)| event).collect()
+    }
+
+    /// Applies the animation at the current time to the model
+    pub fn apply(&mut self, model: &mut Model) -> Result<(), AnimationError> {
+        model.apply_animation_cached(self.animation, self.time(), &mut self.cursor)
+    }
+
+    /// Applies the animation only to nodes included in the mask
+    pub fn apply_masked(
+        &mut self,
+        model: &mut Model,
+        mask: &BoneMask,
+    ) -> Result<(), AnimationError> {
+        model.apply_animation_masked(self.animation, self.time(), mask)
+    }
+
+    /// Applies the animation as an additive layer on top of the current pose, see [Model::apply_additive_animation]
+    pub fn apply_additive(&mut self, model: &mut Model, weight: f32) -> Result<(), AnimationError> {
+        model.apply_additive_animation(self.animation, self.time(), weight)
+    }
+}
+
+/// Smooth transition from one animation to another
+#[derive(Debug, Clone)]
+pub struct Crossfade {
+    pub from: AnimationPlayer,
+    pub to: AnimationPlayer,
+    /// Duration of the transition in seconds
+    pub duration: f32,
+    elapsed: f32,
+}
+
+impl Crossfade {
+    pub fn new(from: AnimationPlayer, to: AnimationPlayer, duration: f32) -> Self {
+        Self {
+            from,
+            to,
+            duration,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Weight of the target animation, goes from 0 to 1 during the transition
+    pub fn weight(&self) -> f32 {
+        if self.duration <= 0.0 {
+            return 1.0;
+        }
+        (self.elapsed / self.duration).clamp(0.0, 1.0)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.weight() >= 1.0
+    }
+
+    /// Returns the target animation player, to continue playing it after the transition
+    pub fn into_target(self) -> AnimationPlayer {
+        self.to
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        self.from.update(delta_time);
+        self.to.update(delta_time);
+        self.elapsed += delta_time;
+    }
+
+    /// Applies both animations, blended by the current [Crossfade::weight]
+    pub fn apply(&self, model: &mut Model) -> Result<(), AnimationError> {
+        let weight = self.weight();
+        model.blend_animations([
+            (self.from.animation, self.from.time(), 1.0 - weight),
+            (self.to.animation, self.to.time(), weight),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(loop_mode: LoopMode, speed: f32) -> AnimationPlayer {
+        AnimationPlayer {
+            speed,
+            loop_mode,
+            paused: false,
+
+            animation: 0,
+            duration: 1.0,
+            elapsed: 0.0,
+            loops: 0,
+            step: (0.0, 0.0),
+            finished: false,
+            cursor: AnimationCursor::new(),
+        }
+    }
+
+    fn events(times: &[(&str, f32)]) -> Vec<AnimationEvent> {
+        times
+            .iter()
+            .map(|&(name, time)| AnimationEvent {
+                name: name.to_owned(),
+                time,
+            })
+            .collect()
+    }
+
+    fn crossed<'a>(
+        player: &mut AnimationPlayer,
+        events: &'a [AnimationEvent],
+        delta_time: f32,
+    ) -> Vec<&'a str> {
+        player.update(delta_time);
+        player
+            .crossed(events)
+            .into_iter()
+            .map(|event| event.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn events_once() {
+        let events = events(&[("start", 0.0), ("middle", 0.5), ("end", 1.0)]);
+        let mut player = player(LoopMode::Once, 1.0);
+        assert_eq!(crossed(&mut player, &events, 0.25), ["start"]);
+        assert_eq!(crossed(&mut player, &events, 0.5), ["middle"]);
+        assert_eq!(crossed(&mut player, &events, 0.5), ["end"]);
+        assert!(player.is_finished());
+        assert!(crossed(&mut player, &events, 0.5).is_empty());
+    }
+
+    #[test]
+    fn events_loop() {
+        let events = events(&[("start", 0.0), ("middle", 0.5)]);
+        let mut player = player(LoopMode::Loop, 1.0);
+        assert_eq!(crossed(&mut player, &events, 0.25), ["start"]);
+        assert_eq!(crossed(&mut player, &events, 0.5), ["middle"]);
+        // The start of the next loop is reported once
+        assert_eq!(crossed(&mut player, &events, 0.5), ["start"]);
+        assert_eq!(crossed(&mut player, &events, 1.0), ["middle", "start"]);
+        assert_eq!(player.loops(), 2);
+    }
+
+    #[test]
+    fn events_ping_pong() {
+        let events = events(&[("start", 0.0), ("middle", 0.5), ("end", 1.0)]);
+        let mut player = player(LoopMode::PingPong, 1.0);
+        assert_eq!(crossed(&mut player, &events, 0.75), ["start", "middle"]);
+        assert_eq!(crossed(&mut player, &events, 0.75), ["end"]);
+        assert_eq!(crossed(&mut player, &events, 0.75), ["middle", "start"]);
+    }
+
+    #[test]
+    fn events_backwards() {
+        let events = events(&[("a", 0.25), ("b", 0.75)]);
+        let mut player = player(LoopMode::Loop, -1.0);
+        assert_eq!(crossed(&mut player, &events, 0.5), ["b"]);
+        assert_eq!(crossed(&mut player, &events, 0.5), ["a"]);
+        assert_eq!(crossed(&mut player, &events, 1.0), ["b", "a"]);
+        assert_eq!(player.loops(), -2);
+    }
+}
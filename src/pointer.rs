@@ -0,0 +1,667 @@
+use super::*;
+
+/// Property of a glTF object (other than a node), animated with the `KHR_animation_pointer` extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnimationPointer {
+    Material {
+        material: usize,
+        property: MaterialProperty,
+    },
+    Camera {
+        camera: usize,
+        property: CameraProperty,
+    },
+    Light {
+        light: usize,
+        property: LightProperty,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MaterialProperty {
+    BaseColor,
+    EmissiveFactor,
+    EmissiveStrength,
+    BaseTextureOffset,
+    BaseTextureRotation,
+    BaseTextureScale,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CameraProperty {
+    YFov,
+    XMag,
+    YMag,
+    ZNear,
+    ZFar,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LightProperty {
+    Color,
+    Intensity,
+    Range,
+    InnerConeAngle,
+    OuterConeAngle,
+}
+
+impl AnimationPointer {
+    /// Parses a JSON pointer, like `/materials/0/emissiveFactor`.
+    /// Returns [None] for pointers to properties, that can't be animated
+    pub fn parse(pointer: &str) -> Option<Self> {
+        let segments = pointer.strip_prefix('/')?.split('/').collect_vec();
+        let pointer = match segments.as_slice() {
+            ["materials", index, path @ ..] => Self::Material {
+                material: index.parse().ok()?,
+                property: match path {
+                    ["pbrMetallicRoughness", "baseColorFactor"] => MaterialProperty::BaseColor,
+                    ["emissiveFactor"] => MaterialProperty::EmissiveFactor,
+                    ["extensions", "KHR_materials_emissive_strength", "emissiveStrength"] => {
+                        MaterialProperty::EmissiveStrength
+                    }
+                    ["pbrMetallicRoughness", "baseColorTexture", "extensions", "KHR_texture_transform", property] => {
+                        match *property {
+                            "offset" => MaterialProperty::BaseTextureOffset,
+                            "rotation" => MaterialProperty::BaseTextureRotation,
+                            "scale" => MaterialProperty::BaseTextureScale,
+                            _ => return None,
+                        }
+                    }
+                    _ => return None,
+                },
+            },
+            ["cameras", index, "perspective" | "orthographic", property] => Self::Camera {
+                camera: index.parse().ok()?,
+                property: match *property {
+                    "yfov" => CameraProperty::YFov,
+                    "xmag" => CameraProperty::XMag,
+                    "ymag" => CameraProperty::YMag,
+                    "znear" => CameraProperty::ZNear,
+                    "zfar" => CameraProperty::ZFar,
+                    _ => return None,
+                },
+            },
+            ["extensions", "KHR_lights_punctual", "lights", index, path @ ..] => Self::Light {
+                light: index.parse().ok()?,
+                property: match path {
+                    ["color"] => LightProperty::Color,
+                    ["intensity"] => LightProperty::Intensity,
+                    ["range"] => LightProperty::Range,
+                    ["spot", "innerConeAngle"] => LightProperty::InnerConeAngle,
+                    ["spot", "outerConeAngle"] => LightProperty::OuterConeAngle,
+                    _ => return None,
+                },
+            },
+            _ => return None,
+        };
+        Some(pointer)
+    }
+}
+
+impl Animation {
+    /// Returns true, if the animation has `KHR_animation_pointer` channels (see [Outputs::Property])
+    pub fn has_properties(&self) -> bool {
+        self.channels
+            .iter()
+            .any(|channel| matches!(channel.outputs, Outputs::Property { .. }))
+    }
+}
+
+impl Channel {
+    /// Loads a `KHR_animation_pointer` channel, that animates the property with the sampler of the animation.
+    /// Returns [None] if the pointer or the sampler isn't supported
+    pub(crate) fn load_pointer(
+        animation: &gltf::Animation<'_>,
+        sampler: usize,
+        pointer: &str,
+        buffers: &[gltf::buffer::Data],
+    ) -> Result<Option<Self>, MeshLoadError> {
+        let Some(parsed) = AnimationPointer::parse(pointer) else {
+            log::warn!("Animating {:?} is not supported, skipping", pointer);
+            return Ok(None);
+        };
+        let Some(sampler) = animation.samplers().nth(sampler) else {
+            log::warn!(
+                "Sampler #{} of animation pointer {:?} not found",
+                sampler,
+                pointer
+            );
+            return Ok(None);
+        };
+        let interpolation = match sampler.interpolation() {
+            gltf::animation::Interpolation::Step => Interpolation::Step,
+            gltf::animation::Interpolation::Linear => Interpolation::Linear,
+            gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+        };
+
+        let get_buffer_data = |buffer: gltf::Buffer| buffers.get(buffer.index()).map(|x| &**x);
+        let inputs: Vec<f32> = gltf::accessor::Iter::<f32>::new(sampler.input(), get_buffer_data)
+            .ok_or(MeshLoadError::MissingAnimationInputs)?
+            .collect();
+
+        // Outputs are stored as one list of values per component
+        let output = sampler.output();
+        fn read<'a, 's, const N: usize>(
+            accessor: gltf::Accessor<'a>,
+            get_buffer_data: impl Clone + Fn(gltf::Buffer<'a>) -> Option<&'s [u8]>,
+        ) -> Option<Vec<Vec<f32>>>
+        where
+            [f32; N]: gltf::accessor::Item,
+        {
+            let values =
+                gltf::accessor::Iter::<[f32; N]>::new(accessor, get_buffer_data)?.collect_vec();
+            Some(
+                (0..N)
+                    .map(|i| values.iter().map(|value| value[i]).collect())
+                    .collect(),
+            )
+        }
+        let outputs = match output.dimensions() {
+            _ if output.data_type() != gltf::accessor::DataType::F32 => None,
+            gltf::accessor::Dimensions::Scalar => {
+                gltf::accessor::Iter::<f32>::new(output, get_buffer_data)
+                    .map(|values| vec![values.collect()])
+            }
+            gltf::accessor::Dimensions::Vec2 => read::<2>(output, get_buffer_data),
+            gltf::accessor::Dimensions::Vec3 => read::<3>(output, get_buffer_data),
+            gltf::accessor::Dimensions::Vec4 => read::<4>(output, get_buffer_data),
+            _ => None,
+        };
+        let outputs = outputs.ok_or(MeshLoadError::MissingAnimationOutputs)?;
+
+        Ok(Some(Self {
+            // Property channels don't animate nodes
+            target: 0,
+            interpolation,
+            inputs,
+            outputs: Outputs::Property {
+                pointer: parsed,
+                outputs,
+            },
+        }))
+    }
+}
+
+impl Model {
+    /// Writes values of `KHR_animation_pointer` channels of the animation at `time`
+    /// into [Model::materials], [Model::cameras] and [Model::lights].
+    /// [Model::apply_animation] already does this, it's needed when animating with poses,
+    /// since they only contain node transforms and morph target weights
+    pub fn apply_animation_properties(
+        &mut self,
+        id: impl Into<Id>,
+        time: f32,
+    ) -> Result<(), AnimationError> {
+        let index = self.animation_index(id)?;
+        for (pointer, values) in self.animations[index].sample_properties(time) {
+            self.set_property(pointer, &values);
+        }
+        Ok(())
+    }
+
+    /// Blends properties of the animations, given as `(animation index, time, weight)`, see [Model::blend_animations].
+    /// Properties, that some animation doesn't animate, use their rest values for it
+    pub(crate) fn apply_blended_properties(&mut self, animations: &[(usize, f32, f32)]) {
+        let total_weight: f32 = animations.iter().map(|&(_, _, weight)| weight).sum();
+        if total_weight <= 0.0 {
+            return;
+        }
+
+        // Weighted sums of values and weights of the animations, that animate the property
+        let mut blended: Vec<(AnimationPointer, Vec<f32>, f32)> = Vec::new();
+        for &(index, time, weight) in animations {
+            for (pointer, values) in self.animations[index].sample_properties(time) {
+                let position = match blended.iter().position(|(other, ..)| *other == pointer) {
+                    Some(position) => position,
+                    None => {
+                        blended.push((pointer, vec![0.0; values.len()], 0.0));
+                        blended.len() - 1
+                    }
+                };
+                let (_, sum, covered) = &mut blended[position];
+                for (sum, value) in std::iter::zip(sum, values) {
+                    *sum += value * weight;
+                }
+                *covered += weight;
+            }
+        }
+
+        for (pointer, sum, covered) in blended {
+            let rest = self.rest_property(pointer);
+            let values = sum
+                .iter()
+                .enumerate()
+                .map(|(index, sum)| {
+                    let rest = rest.get(index).copied().unwrap_or(0.0);
+                    (sum + rest * (total_weight - covered)) / total_weight
+                })
+                .collect_vec();
+            self.set_property(pointer, &values);
+        }
+    }
+
+    /// Restores properties, that were animated, to their values from before the first animation
+    pub(crate) fn reset_properties(&mut self) {
+        for (pointer, values) in std::mem::take(&mut self.rest_properties) {
+            self.write_property(pointer, &values);
+        }
+    }
+
+    /// Value of the property before it was first animated
+    fn rest_property(&self, pointer: AnimationPointer) -> Vec<f32> {
+        match self.rest_properties.get(&pointer) {
+            Some(values) => values.clone(),
+            None => self.property(pointer),
+        }
+    }
+
+    /// Writes the animated value of the property, remembering it's rest value the first time
+    fn set_property(&mut self, pointer: AnimationPointer, values: &[f32]) {
+        if !self.rest_properties.contains_key(&pointer) {
+            let rest = self.property(pointer);
+            self.rest_properties.insert(pointer, rest);
+        }
+        self.write_property(pointer, values);
+    }
+
+    /// Current value of the property, one value per component.
+    /// Empty if the object doesn't exist or doesn't have the property (e.g. a light without a range)
+    fn property(&self, pointer: AnimationPointer) -> Vec<f32> {
+        match pointer {
+            AnimationPointer::Material { material, property } => {
+                let Some(material) = self.materials.get(material) else {
+                    return Vec::new();
+                };
+                match property {
+                    MaterialProperty::BaseColor => {
+                        let color = material.base_color;
+                        vec![color.r, color.g, color.b, color.a]
+                    }
+                    MaterialProperty::EmissiveFactor => {
+                        let factor = material.emissive_factor;
+                        vec![factor.x, factor.y, factor.z]
+                    }
+                    MaterialProperty::EmissiveStrength => vec![material.emissive_strength],
+                    MaterialProperty::BaseTextureOffset => {
+                        let offset = material.base_texture_transform.offset;
+                        vec![offset.x, offset.y]
+                    }
+                    MaterialProperty::BaseTextureRotation => {
+                        vec![material.base_texture_transform.rotation]
+                    }
+                    MaterialProperty::BaseTextureScale => {
+                        let scale = material.base_texture_transform.scale;
+                        vec![scale.x, scale.y]
+                    }
+                }
+            }
+            AnimationPointer::Camera { camera, property } => {
+                let Some(camera) = self.cameras.get(camera) else {
+                    return Vec::new();
+                };
+                match (camera, property) {
+                    (Projection::Perspective { y_fov, .. }, CameraProperty::YFov) => vec![*y_fov],
+                    (Projection::Orthographic { x_mag, .. }, CameraProperty::XMag) => vec![*x_mag],
+                    (Projection::Orthographic { y_mag, .. }, CameraProperty::YMag) => vec![*y_mag],
+                    (
+                        Projection::Perspective { z_near, .. }
+                        | Projection::Orthographic { z_near, .. },
+                        CameraProperty::ZNear,
+                    ) => vec![*z_near],
+                    (
+                        Projection::Perspective { z_far, .. }
+                        | Projection::Orthographic { z_far, .. },
+                        CameraProperty::ZFar,
+                    ) => vec![*z_far],
+                    _ => Vec::new(),
+                }
+            }
+            AnimationPointer::Light { light, property } => {
+                let Some(light) = self.lights.get(light) else {
+                    return Vec::new();
+                };
+                match (&light.kind, property) {
+                    (_, LightProperty::Color) => {
+                        vec![light.color.r, light.color.g, light.color.b]
+                    }
+                    (_, LightProperty::Intensity) => vec![light.intensity],
+                    (_, LightProperty::Range) => light.range.into_iter().collect(),
+                    (
+                        LightKind::Spot {
+                            inner_cone_angle, ..
+                        },
+                        LightProperty::InnerConeAngle,
+                    ) => vec![*inner_cone_angle],
+                    (
+                        LightKind::Spot {
+                            outer_cone_angle, ..
+                        },
+                        LightProperty::OuterConeAngle,
+                    ) => vec![*outer_cone_angle],
+                    _ => Vec::new(),
+                }
+            }
+        }
+    }
+
+    /// Writes the value of the property, missing components are zero.
+    /// Writing no values to the range of a light removes it
+    fn write_property(&mut self, pointer: AnimationPointer, values: &[f32]) {
+        let value = |index: usize| values.get(index).copied().unwrap_or(0.0);
+        match pointer {
+            AnimationPointer::Material { material, property } => {
+                let Some(material) = self.materials.get_mut(material) else {
+                    return;
+                };
+                match property {
+                    MaterialProperty::BaseColor => {
+                        material.base_color = Rgba::new(value(0), value(1), value(2), value(3));
+                    }
+                    MaterialProperty::EmissiveFactor => {
+                        material.emissive_factor = vec3(value(0), value(1), value(2));
+                    }
+                    MaterialProperty::EmissiveStrength => material.emissive_strength = value(0),
+                    MaterialProperty::BaseTextureOffset => {
+                        material.base_texture_transform.offset = vec2(value(0), value(1));
+                    }
+                    MaterialProperty::BaseTextureRotation => {
+                        material.base_texture_transform.rotation = value(0);
+                    }
+                    MaterialProperty::BaseTextureScale => {
+                        material.base_texture_transform.scale = vec2(value(0), value(1));
+                    }
+                }
+            }
+            AnimationPointer::Camera { camera, property } => {
+                let Some(camera) = self.cameras.get_mut(camera) else {
+                    return;
+                };
+                match (camera, property) {
+                    (Projection::Perspective { y_fov, .. }, CameraProperty::YFov) => {
+                        *y_fov = value(0);
+                    }
+                    (Projection::Orthographic { x_mag, .. }, CameraProperty::XMag) => {
+                        *x_mag = value(0);
+                    }
+                    (Projection::Orthographic { y_mag, .. }, CameraProperty::YMag) => {
+                        *y_mag = value(0);
+                    }
+                    (
+                        Projection::Perspective { z_near, .. }
+                        | Projection::Orthographic { z_near, .. },
+                        CameraProperty::ZNear,
+                    ) => *z_near = value(0),
+                    (
+                        Projection::Perspective { z_far, .. }
+                        | Projection::Orthographic { z_far, .. },
+                        CameraProperty::ZFar,
+                    ) => *z_far = value(0),
+                    _ => {}
+                }
+            }
+            AnimationPointer::Light { light, property } => {
+                let Some(light) = self.lights.get_mut(light) else {
+                    return;
+                };
+                match (&mut light.kind, property) {
+                    (_, LightProperty::Color) => {
+                        light.color = Rgba::new(value(0), value(1), value(2), 1.0);
+                    }
+                    (_, LightProperty::Intensity) => light.intensity = value(0),
+                    (_, LightProperty::Range) => light.range = values.first().copied(),
+                    (
+                        LightKind::Spot {
+                            inner_cone_angle, ..
+                        },
+                        LightProperty::InnerConeAngle,
+                    ) => *inner_cone_angle = value(0),
+                    (
+                        LightKind::Spot {
+                            outer_cone_angle, ..
+                        },
+                        LightProperty::OuterConeAngle,
+                    ) => *outer_cone_angle = value(0),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+impl Animation {
+    /// Values of all animated properties at `time`
+    fn sample_properties(&self, time: f32) -> Vec<(AnimationPointer, Vec<f32>)> {
+        self.channels
+            .iter()
+            .filter_map(|channel| {
+                let Outputs::Property { pointer, outputs } = &channel.outputs else {
+                    return None;
+                };
+                let values = outputs
+                    .iter()
+                    .map(|outputs| channel.sample(time, outputs, lerp_f32))
+                    .collect();
+                Some((*pointer, values))
+            })
+            .collect()
+    }
+}
+
+/// `KHR_animation_pointer` channels of every animation. The gltf crate can't parse them, since they don't
+/// target a node, so [import_gltf] takes them out of the JSON and they are loaded separately
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PointerChannels {
+    /// Sampler index and pointer of every channel, for every animation
+    animations: Vec<Vec<(usize, String)>>,
+}
+
+impl PointerChannels {
+    /// Removes pointer channels from the animations in the JSON.
+    /// Pointers to node transforms and weights are turned into regular channels instead
+    fn take(json: &mut serde_json::Value) -> Self {
+        let mut pointers = Self::default();
+        let Some(animations) = json.get_mut("animations").and_then(|x| x.as_array_mut()) else {
+            return pointers;
+        };
+
+        for animation in animations {
+            let mut animation_pointers = Vec::new();
+            if let Some(channels) = animation.get_mut("channels").and_then(|x| x.as_array_mut()) {
+                channels.retain_mut(|channel| {
+                    if channel["target"]["path"] != "pointer" {
+                        return true;
+                    }
+                    let pointer = channel["target"]["extensions"]["KHR_animation_pointer"]
+                        ["pointer"]
+                        .as_str()
+                        .map(str::to_owned);
+                    let (Some(pointer), Some(sampler)) = (pointer, channel["sampler"].as_u64())
+                    else {
+                        log::warn!("Animation pointer channel without a pointer, skipping");
+                        return false;
+                    };
+
+                    let segments = pointer.split('/').collect_vec();
+                    if let [
+                        "",
+                        "nodes",
+                        node,
+                        path @ ("translation" | "rotation" | "scale" | "weights"),
+                    ] = segments.as_slice()
+                    {
+                        if let Ok(node) = node.parse::<usize>() {
+                            channel["target"] = serde_json::json!({ "node": node, "path": path });
+                            return true;
+                        }
+                    }
+
+                    animation_pointers.push((sampler as usize, pointer));
+                    false
+                });
+            }
+            pointers.animations.push(animation_pointers);
+        }
+        pointers
+    }
+
+    /// Sampler index and pointer of every pointer channel of the animation
+    pub(crate) fn get(&self, animation: usize) -> &[(usize, String)] {
+        self.animations.get(animation).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Like [gltf::import_slice], but also returns `KHR_animation_pointer` channels, that the gltf crate can't parse.
+/// `base` is the directory, that external buffers and images are relative to
+pub(crate) fn import_gltf(
+    bytes: &[u8],
+    base: Option<&std::path::Path>,
+) -> Result<
+    (
+        (
+            gltf::Document,
+            Vec<gltf::buffer::Data>,
+            Vec<gltf::image::Data>,
+        ),
+        PointerChannels,
+    ),
+    MeshLoadError,
+> {
+    let (json, blob) = if bytes.starts_with(b"glTF") {
+        let glb = gltf::Glb::from_slice(bytes)?;
+        (glb.json.into_owned(), glb.bin.map(|bin| bin.into_owned()))
+    } else {
+        (bytes.to_vec(), None)
+    };
+
+    let mut json: serde_json::Value =
+        serde_json::from_slice(&json).map_err(gltf::Error::Deserialize)?;
+    let pointers = PointerChannels::take(&mut json);
+    let json: gltf::json::Root = serde_json::from_value(json).map_err(gltf::Error::Deserialize)?;
+
+    let document = gltf::Document::from_json(json)?;
+    let buffers = gltf::import_buffers(&document, base, blob)?;
+    let images = gltf::import_images(&document, base, &buffers)?;
+    Ok(((document, buffers, images), pointers))
+}
+
+/// Like [import_gltf], but reads the file, external files are relative to it
+pub(crate) fn import_gltf_path(
+    path: &std::path::Path,
+) -> Result<
+    (
+        (
+            gltf::Document,
+            Vec<gltf::buffer::Data>,
+            Vec<gltf::image::Data>,
+        ),
+        PointerChannels,
+    ),
+    MeshLoadError,
+> {
+    let bytes = std::fs::read(path).map_err(gltf::Error::Io)?;
+    import_gltf(
+        &bytes,
+        Some(path.parent().unwrap_or(std::path::Path::new("./"))),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_supported_pointers() {
+        assert_eq!(
+            AnimationPointer::parse("/materials/0/emissiveFactor"),
+            Some(AnimationPointer::Material {
+                material: 0,
+                property: MaterialProperty::EmissiveFactor,
+            })
+        );
+        assert_eq!(
+            AnimationPointer::parse(
+                "/materials/2/pbrMetallicRoughness/baseColorTexture/extensions/KHR_texture_transform/offset"
+            ),
+            Some(AnimationPointer::Material {
+                material: 2,
+                property: MaterialProperty::BaseTextureOffset,
+            })
+        );
+        assert_eq!(
+            AnimationPointer::parse("/cameras/1/perspective/yfov"),
+            Some(AnimationPointer::Camera {
+                camera: 1,
+                property: CameraProperty::YFov,
+            })
+        );
+        assert_eq!(
+            AnimationPointer::parse("/extensions/KHR_lights_punctual/lights/2/spot/outerConeAngle"),
+            Some(AnimationPointer::Light {
+                light: 2,
+                property: LightProperty::OuterConeAngle,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_rejected_pointers() {
+        for pointer in [
+            "/materials/0/alphaCutoff",
+            "/materials/first/emissiveFactor",
+            "materials/0/emissiveFactor",
+            "/cameras/0/perspective/aspectRatio",
+            "/extensions/KHR_lights_punctual/lights/0/spot",
+            // Node pointers are turned into regular channels when importing
+            "/nodes/0/translation",
+        ] {
+            assert_eq!(AnimationPointer::parse(pointer), None, "{pointer}");
+        }
+    }
+
+    #[test]
+    fn take_pointer_channels() {
+        let mut json = serde_json::json!({
+            "animations": [
+                {
+                    "channels": [
+                        {
+                            "sampler": 0,
+                            "target": {
+                                "path": "pointer",
+                                "extensions": {
+                                    "KHR_animation_pointer": {
+                                        "pointer": "/materials/0/emissiveFactor"
+                                    }
+                                }
+                            }
+                        },
+                        {
+                            "sampler": 1,
+                            "target": {
+                                "path": "pointer",
+                                "extensions": {
+                                    "KHR_animation_pointer": { "pointer": "/nodes/3/rotation" }
+                                }
+                            }
+                        },
+                        { "sampler": 2, "target": { "node": 1, "path": "scale" } }
+                    ]
+                }
+            ]
+        });
+
+        let pointers = PointerChannels::take(&mut json);
+        assert_eq!(
+            pointers.get(0),
+            [(0, "/materials/0/emissiveFactor".to_owned())]
+        );
+        assert!(pointers.get(1).is_empty());
+        assert_eq!(
+            json["animations"][0]["channels"],
+            serde_json::json!([
+                { "sampler": 1, "target": { "node": 3, "path": "rotation" } },
+                { "sampler": 2, "target": { "node": 1, "path": "scale" } }
+            ])
+        );
+    }
+}
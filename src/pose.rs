@@ -0,0 +1,187 @@
+use super::*;
+
+/// Local transforms and morph target weights of every node,
+/// sampled from animations. Poses don't need a [Model] or GPU context,
+/// so they can be freely sampled, blended, cached and then applied to any number of models
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pose {
+    pub transforms: Vec<Transform>,
+    pub weights: Vec<Vec<f32>>,
+}
+
+impl Pose {
+    pub fn new(transforms: Vec<Transform>, weights: Vec<Vec<f32>>) -> Self {
+        Self {
+            transforms,
+            weights,
+        }
+    }
+
+    /// Blends `other` into this pose. Weight of 0 keeps this pose, 1 replaces it with `other`
+    pub fn blend(&mut self, other: &Pose, weight: f32) {
+        for (transform, other) in std::iter::zip(&mut self.transforms, &other.transforms) {
+            *transform = transform.lerp(other, weight);
+        }
+        for (weights, other) in std::iter::zip(&mut self.weights, &other.weights) {
+            for (value, other) in std::iter::zip(weights, other) {
+                *value += (other - *value) * weight;
+            }
+        }
+    }
+
+    /// Weighted average of several poses, see [Transform::blend].
+    /// Returns [None] if there are no poses or their weights sum up to zero
+    pub fn weighted<'a>(poses: impl IntoIterator<Item = (&'a Pose, f32)>) -> Option<Pose> {
+        let poses = poses.into_iter().collect_vec();
+        let total_weight: f32 = poses.iter().map(|(_, weight)| weight).sum();
+        if total_weight <= 0.0 {
+            return None;
+        }
+
+        let mut result = poses[0].0.clone();
+        for (node, transform) in result.transforms.iter_mut().enumerate() {
+            *transform = Transform::blend(
+                poses
+                    .iter()
+                    .map(|(pose, weight)| (pose.transforms[node], *weight)),
+            );
+        }
+        for (node, weights) in result.weights.iter_mut().enumerate() {
+            for (target, value) in weights.iter_mut().enumerate() {
+                *value = poses
+                    .iter()
+                    .map(|(pose, weight)| pose.weights[node][target] * weight)
+                    .sum::<f32>()
+                    / total_weight;
+            }
+        }
+        Some(result)
+    }
+
+    /// Adds the difference between `additive` and `reference` poses on top of this one, scaled by `weight`
+    pub fn add(&mut self, additive: &Pose, reference: &Pose, weight: f32) {
+        for ((transform, additive), reference) in
+            std::iter::zip(&mut self.transforms, &additive.transforms).zip(&reference.transforms)
+        {
+            *transform = transform.add(additive, reference, weight);
+        }
+        for ((weights, additive), reference) in
+            std::iter::zip(&mut self.weights, &additive.weights).zip(&reference.weights)
+        {
+            for ((value, additive), reference) in std::iter::zip(weights, additive).zip(reference) {
+                *value += (additive - reference) * weight;
+            }
+        }
+    }
+
+    /// Writes this pose into the model
+    pub fn apply(&self, model: &mut Model) {
+        model.transforms.clone_from(&self.transforms);
+        model.weights.clone_from(&self.weights);
+    }
+}
+
+impl Animation {
+    /// Samples the animation at `time` into the pose.
+    /// Components, that are not animated, keep their values.
+    /// Animated material, camera and light properties are not part of the pose,
+    /// see [Model::apply_animation_properties]
+    pub fn sample(&self, time: f32, pose: &mut Pose) {
+        self.sample_into(time, &mut pose.transforms, &mut pose.weights);
+    }
+}
+
+impl Model {
+    /// Pose with rest transforms and default morph target weights
+    pub fn rest_pose(&self) -> Pose {
+        Pose::new(self.rest_transforms.clone(), self.default_weights.clone())
+    }
+
+    /// Current pose of the model
+    pub fn pose(&self) -> Pose {
+        Pose::new(self.transforms.clone(), self.weights.clone())
+    }
+
+    pub fn apply_pose(&mut self, pose: &Pose) {
+        pose.apply(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translation(x: f32) -> Transform {
+        Transform {
+            translation: vec3(x, 0.0, 0.0),
+            ..Transform::identity()
+        }
+    }
+
+    #[test]
+    fn sample_keeps_not_animated_nodes() {
+        let animation = Animation::new(vec![Channel {
+            target: 1,
+            interpolation: Interpolation::Linear,
+            inputs: vec![0.0, 1.0],
+            outputs: Outputs::Translation(vec![vec3(0.0, 0.0, 0.0), vec3(2.0, 0.0, 0.0)]),
+        }]);
+        let mut pose = Pose::new(vec![translation(5.0), translation(5.0)], vec![vec![0.5]; 2]);
+        animation.sample(0.5, &mut pose);
+        assert_eq!(pose.transforms, vec![translation(5.0), translation(1.0)]);
+        assert_eq!(pose.weights, vec![vec![0.5]; 2]);
+    }
+
+    #[test]
+    fn sample_morph_weights() {
+        let animation = Animation::new(vec![Channel {
+            target: 1,
+            interpolation: Interpolation::Linear,
+            inputs: vec![0.0, 1.0],
+            outputs: Outputs::Weights(vec![vec![0.0, 1.0], vec![1.0, 0.0]]),
+        }]);
+        let mut pose = Pose::new(
+            vec![Transform::identity(); 2],
+            vec![Vec::new(), vec![0.0; 2]],
+        );
+        animation.sample(0.25, &mut pose);
+        assert_eq!(pose.weights, vec![Vec::new(), vec![0.25, 0.75]]);
+        assert_eq!(pose.transforms, vec![Transform::identity(); 2]);
+    }
+
+    #[test]
+    fn blend() {
+        let mut pose = Pose::new(vec![translation(0.0)], vec![vec![0.0]]);
+        let other = Pose::new(vec![translation(4.0)], vec![vec![1.0]]);
+        pose.blend(&other, 0.25);
+        assert_eq!(pose, Pose::new(vec![translation(1.0)], vec![vec![0.25]]));
+    }
+
+    #[test]
+    fn weighted() {
+        let a = Pose::new(vec![translation(0.0)], vec![vec![0.0]]);
+        let b = Pose::new(vec![translation(3.0)], vec![vec![1.0]]);
+        let blended = Pose::weighted([(&a, 2.0), (&b, 1.0)]).unwrap();
+        assert!((blended.transforms[0].translation.x - 1.0).abs() < 1e-5);
+        assert!((blended.weights[0][0] - 1.0 / 3.0).abs() < 1e-5);
+        assert_eq!(
+            vec4::dot(
+                blended.transforms[0].rotation,
+                blended.transforms[0].rotation
+            ),
+            1.0
+        );
+
+        assert!(Pose::weighted([(&a, 0.0), (&b, 0.0)]).is_none());
+        assert!(Pose::weighted(std::iter::empty()).is_none());
+    }
+
+    #[test]
+    fn add() {
+        let reference = Pose::new(vec![translation(1.0)], vec![vec![0.0]]);
+        let additive = Pose::new(vec![translation(3.0)], vec![vec![0.5]]);
+        let mut pose = Pose::new(vec![translation(10.0)], vec![vec![0.25]]);
+        pose.add(&additive, &reference, 0.5);
+        assert_eq!(pose, Pose::new(vec![translation(11.0)], vec![vec![0.5]]));
+    }
+}
@@ -8,8 +8,37 @@ attribute vec4 a_color;
 attribute vec4 a_joints;
 attribute vec4 a_weights;
 
+// Deltas of the first 4 morph targets, only bound for primitives that have morph targets.
+// Shaders calling morph_pos() or morph_normal() use 14 vertex attributes,
+// while GLES2/WebGL1 only guarantee 8 (GL_MAX_VERTEX_ATTRIBS), so don't use them on such devices
+attribute vec3 a_morph_pos0;
+attribute vec3 a_morph_pos1;
+attribute vec3 a_morph_pos2;
+attribute vec3 a_morph_pos3;
+attribute vec3 a_morph_normal0;
+attribute vec3 a_morph_normal1;
+attribute vec3 a_morph_normal2;
+attribute vec3 a_morph_normal3;
+
 uniform mat4 u_joint_matrices[100];
 uniform mat4 u_model_matrix;
+uniform vec4 u_morph_weights;
+
+vec3 morph_pos() {
+    return a_pos +
+        u_morph_weights.x * a_morph_pos0 +
+        u_morph_weights.y * a_morph_pos1 +
+        u_morph_weights.z * a_morph_pos2 +
+        u_morph_weights.w * a_morph_pos3;
+}
+
+vec3 morph_normal() {
+    return a_normal +
+        u_morph_weights.x * a_morph_normal0 +
+        u_morph_weights.y * a_morph_normal1 +
+        u_morph_weights.z * a_morph_normal2 +
+        u_morph_weights.w * a_morph_normal3;
+}
 
 mat4 skin_matrix() {
     if (a_weights.x == 0.0 && a_weights.y == 0.0 && a_weights.z == 0.0 && a_weights.w == 0.0) {
@@ -20,4 +49,47 @@ mat4 skin_matrix() {
         a_weights.z * u_joint_matrices[int(a_joints.z)] +
         a_weights.w * u_joint_matrices[int(a_joints.w)];
 }
+
+// Baked animation, see `BakedAnimation` and `Model::draw_baked`
+uniform sampler2D u_baked_joints;
+uniform vec2 u_baked_size;
+uniform float u_baked_frame_rate;
+uniform float u_baked_duration;
+uniform float u_baked_time;
+
+mat4 baked_joint_matrix(float joint, float frame) {
+    vec2 texel = vec2(joint * 4.0 + 0.5, frame + 0.5) / u_baked_size;
+    float texel_width = 1.0 / u_baked_size.x;
+    return mat4(
+        texture2D(u_baked_joints, texel),
+        texture2D(u_baked_joints, texel + vec2(texel_width, 0.0)),
+        texture2D(u_baked_joints, texel + vec2(2.0 * texel_width, 0.0)),
+        texture2D(u_baked_joints, texel + vec2(3.0 * texel_width, 0.0)));
+}
+
+mat4 baked_frame_matrix(float frame) {
+    return a_weights.x * baked_joint_matrix(a_joints.x, frame) +
+        a_weights.y * baked_joint_matrix(a_joints.y, frame) +
+        a_weights.z * baked_joint_matrix(a_joints.z, frame) +
+        a_weights.w * baked_joint_matrix(a_joints.w, frame);
+}
+
+// Like skin_matrix(), but joint matrices come from the baked animation, which loops.
+// Different time offsets make instances play the animation out of sync
+mat4 baked_skin_matrix(float time_offset) {
+    if (a_weights.x == 0.0 && a_weights.y == 0.0 && a_weights.z == 0.0 && a_weights.w == 0.0) {
+        return mat4(1.0);
+    }
+    float time = u_baked_time + time_offset;
+    if (u_baked_duration > 0.0) {
+        time = mod(time, u_baked_duration);
+    }
+    float frame = time * u_baked_frame_rate;
+    float last_frame = u_baked_size.y - 1.0;
+    float prev = min(floor(frame), last_frame);
+    float next = min(prev + 1.0, last_frame);
+    // mix() is not defined for matrices in GLSL ES 1.00
+    float f = frame - prev;
+    return baked_frame_matrix(prev) * (1.0 - f) + baked_frame_matrix(next) * f;
+}
 #endif
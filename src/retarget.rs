@@ -0,0 +1,267 @@
+use super::*;
+
+/// Node hierarchy info, needed to retarget animations between models
+#[derive(Debug, Clone)]
+pub struct Skeleton {
+    pub names: Vec<Option<String>>,
+    pub rest_transforms: Vec<Transform>,
+    /// Index of the mesh of every node, if it has one
+    pub meshes: Vec<Option<usize>>,
+}
+
+impl Skeleton {
+    pub fn from_document(document: &gltf::Document) -> Self {
+        Self {
+            names: document
+                .nodes()
+                .map(|node| node.name().map(str::to_owned))
+                .collect(),
+            rest_transforms: document
+                .nodes()
+                .map(|node| Transform::from(node.transform()))
+                .collect(),
+            meshes: document
+                .nodes()
+                .map(|node| node.mesh().map(|mesh| mesh.index()))
+                .collect(),
+        }
+    }
+
+    /// Finds a node by name
+    pub fn node_index(&self, name: &str) -> Option<usize> {
+        find_node(self.names.iter().map(Option::as_deref), name)
+    }
+}
+
+impl Model {
+    pub fn skeleton(&self) -> Skeleton {
+        let mut skeleton = Skeleton::from_document(&self.document);
+        skeleton.rest_transforms.clone_from(&self.rest_transforms);
+        skeleton
+    }
+}
+
+impl Animation {
+    /// Maps channels of an animation, authored for the `source` skeleton, onto the `target` skeleton by node names.
+    /// `name_map` maps source node names to target node names, names that are not in the map are matched as is.
+    ///
+    /// Rotations are applied relative to rest orientations of the nodes,
+    /// translations are scaled by the ratio of bone lengths (distance from the parent at rest).
+    ///
+    /// Animated properties of materials, cameras and lights can't be matched, so they are skipped.
+    ///
+    /// Returns the retargeted animation and names of source nodes (or skipped properties), that couldn't be matched
+    pub fn retarget(
+        &self,
+        source: &Skeleton,
+        target: &Skeleton,
+        name_map: &HashMap<String, String>,
+    ) -> (Animation, Vec<String>) {
+        let mut channels = Vec::new();
+        let mut unmatched = Vec::new();
+        for channel in &self.channels {
+            if let Outputs::Property { pointer, .. } = &channel.outputs {
+                // Indices of materials, cameras and lights can't be matched by node names
+                unmatched.push(format!("{:?}", pointer));
+                continue;
+            }
+
+            let source_name = source.names.get(channel.target).cloned().flatten();
+            let target_index = source_name.as_deref().and_then(|name| {
+                target.node_index(name_map.get(name).map_or(name, String::as_str))
+            });
+            let source_name = source_name.unwrap_or_else(|| format!("#{}", channel.target));
+            let Some(target_index) = target_index else {
+                unmatched.push(source_name);
+                continue;
+            };
+
+            let source_rest = source.rest_transforms[channel.target];
+            let target_rest = target.rest_transforms[target_index];
+            // Cubic spline tangents are derivatives, so they are only scaled and not offset
+            let is_tangent = |index: usize| {
+                channel.interpolation == Interpolation::CubicSpline && index % 3 != 1
+            };
+
+            let outputs = match &channel.outputs {
+                Outputs::Translation(translations) => {
+                    let source_length = source_rest.translation.len();
+                    let ratio = if source_length > 1e-6 {
+                        target_rest.translation.len() / source_length
+                    } else {
+                        1.0
+                    };
+                    Outputs::Translation(
+                        translations
+                            .iter()
+                            .enumerate()
+                            .map(|(index, &translation)| {
+                                if is_tangent(index) {
+                                    translation * ratio
+                                } else {
+                                    target_rest.translation
+                                        + (translation - source_rest.translation) * ratio
+                                }
+                            })
+                            .collect(),
+                    )
+                }
+                Outputs::Rotation(rotations) => {
+                    // Rotation relative to the rest orientation, in parent space
+                    let correction =
+                        quat_mul(quat_conjugate(source_rest.rotation), target_rest.rotation);
+                    Outputs::Rotation(
+                        rotations
+                            .iter()
+                            .map(|&rotation| quat_mul(rotation, correction))
+                            .collect(),
+                    )
+                }
+                Outputs::QuantizedRotation(rotations) => {
+                    let correction =
+                        quat_mul(quat_conjugate(source_rest.rotation), target_rest.rotation);
+                    Outputs::QuantizedRotation(
+                        rotations
+                            .iter()
+                            .map(|&rotation| {
+                                quantize_rotation(quat_mul(
+                                    dequantize_rotation(rotation),
+                                    correction,
+                                ))
+                            })
+                            .collect(),
+                    )
+                }
+                Outputs::Scale(scales) => {
+                    let ratio = |source: f32, target: f32| {
+                        if source != 0.0 {
+                            target / source
+                        } else {
+                            1.0
+                        }
+                    };
+                    let ratio = vec3(
+                        ratio(source_rest.scale.x, target_rest.scale.x),
+                        ratio(source_rest.scale.y, target_rest.scale.y),
+                        ratio(source_rest.scale.z, target_rest.scale.z),
+                    );
+                    Outputs::Scale(
+                        scales
+                            .iter()
+                            .map(|scale| {
+                                vec3(scale.x * ratio.x, scale.y * ratio.y, scale.z * ratio.z)
+                            })
+                            .collect(),
+                    )
+                }
+                // Already reported as unmatched above
+                Outputs::Property { .. } => continue,
+                Outputs::Weights(outputs) => {
+                    if target.meshes[target_index].is_none() {
+                        unmatched.push(source_name);
+                        continue;
+                    }
+                    Outputs::Weights(outputs.clone())
+                }
+            };
+
+            channels.push(Channel {
+                target: target_index,
+                interpolation: channel.interpolation,
+                inputs: channel.inputs.clone(),
+                outputs,
+            });
+        }
+
+        let animation = Animation {
+            name: self.name.clone(),
+            channels,
+            events: self.events.clone(),
+            clips: self.clips.clone(),
+            additive: None,
+        };
+        (animation, unmatched)
+    }
+}
+
+/// Result of [Model::import_animations]
+#[derive(Debug, Clone, Default)]
+pub struct ImportReport {
+    /// Indices of imported animations in [Model::animations]
+    pub animations: Vec<usize>,
+    /// Names of nodes, that are animated in the imported file, but are missing in this model.
+    /// Also includes nodes with animated morph target weights, that have no mesh in this model,
+    /// and animated material, camera and light properties, which are not imported
+    pub unmatched: Vec<String>,
+}
+
+impl Model {
+    /// Imports animations from another glTF file (e.g. one containing only the skeleton),
+    /// mapping them onto nodes of this model by name
+    pub fn import_animations(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<ImportReport, MeshLoadError> {
+        let (gltf, pointers) = import_gltf_path(path.as_ref())?;
+        self.import_animations_with_pointers(gltf, &pointers)
+    }
+
+    pub fn import_animations_from_slice(
+        &mut self,
+        bytes: impl AsRef<[u8]>,
+    ) -> Result<ImportReport, MeshLoadError> {
+        let (gltf, pointers) = import_gltf(bytes.as_ref(), None)?;
+        self.import_animations_with_pointers(gltf, &pointers)
+    }
+
+    pub fn import_animations_from_gltf(
+        &mut self,
+        gltf: (
+            gltf::Document,
+            Vec<gltf::buffer::Data>,
+            Vec<gltf::image::Data>,
+        ),
+    ) -> Result<ImportReport, MeshLoadError> {
+        self.import_animations_with_pointers(gltf, &PointerChannels::default())
+    }
+
+    fn import_animations_with_pointers(
+        &mut self,
+        (document, buffers, _images): (
+            gltf::Document,
+            Vec<gltf::buffer::Data>,
+            Vec<gltf::image::Data>,
+        ),
+        pointers: &PointerChannels,
+    ) -> Result<ImportReport, MeshLoadError> {
+        let source = Skeleton::from_document(&document);
+        let target = self.skeleton();
+
+        let mut report = ImportReport::default();
+        let mut sub_clips = Vec::new();
+        for animation in document.animations() {
+            let pointers = pointers.get(animation.index());
+            let animation = Animation::load_with_pointers(animation, &buffers, pointers)?;
+            let (animation, unmatched) = animation.retarget(&source, &target, &HashMap::new());
+            for name in unmatched {
+                if !report.unmatched.contains(&name) {
+                    report.unmatched.push(name);
+                }
+            }
+            sub_clips.extend(animation.clips.iter().map(|clip| animation.sub_clip(clip)));
+            report.animations.push(self.add_animation(animation));
+        }
+        // Like when loading a model, sub-clips go after all imported animations
+        for animation in sub_clips {
+            report.animations.push(self.add_animation(animation));
+        }
+
+        if !report.unmatched.is_empty() {
+            log::warn!(
+                "Imported animations target nodes, missing in the model: {:?}",
+                report.unmatched
+            );
+        }
+        Ok(report)
+    }
+}
@@ -0,0 +1,117 @@
+use super::*;
+
+/// Horizontal movement and turning of the root node, extracted from an animation.
+/// glTF is Y-up, so horizontal means XZ plane and yaw is the rotation around Y axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RootMotion {
+    /// Translation relative to the root's heading at the start of the motion
+    pub translation: vec3<f32>,
+    pub yaw: Angle<f32>,
+}
+
+impl RootMotion {
+    pub const ZERO: Self = Self {
+        translation: vec3::ZERO,
+        yaw: Angle::ZERO,
+    };
+
+    /// Motion of the root from one transform to another
+    pub fn between(from: &Transform, to: &Transform) -> Self {
+        let from_yaw = yaw(from.rotation);
+        let delta = to.translation - from.translation;
+        Self {
+            translation: rotate_y(vec3(delta.x, 0.0, delta.z), -from_yaw),
+            yaw: Angle::from_radians(normalize_angle(yaw(to.rotation) - from_yaw)),
+        }
+    }
+
+    /// This motion followed by the other one
+    pub fn then(self, other: Self) -> Self {
+        Self {
+            translation: self.translation + rotate_y(other.translation, self.yaw.as_radians()),
+            yaw: Angle::from_radians(self.yaw.as_radians() + other.yaw.as_radians()),
+        }
+    }
+}
+
+impl AnimationPlayer {
+    /// Applies the animation and extracts motion of the `root` node since the previous [AnimationPlayer::update].
+    /// The root is kept in place horizontally and doesn't turn, move your entity by the returned motion instead
+    pub fn apply_with_root_motion(
+        &mut self,
+        model: &mut Model,
+        root: impl Into<Id>,
+    ) -> Result<RootMotion, AnimationError> {
+        let root = model.node_index(root)?;
+        self.apply(model)?;
+
+        let animation = &model.animations[self.animation];
+        let rest = model.rest_transforms[root];
+        let sample = |time: f32| {
+            let mut transform = rest;
+            animation.sample_node(time, root, &mut transform);
+            transform
+        };
+        let motion = |from: f32, to: f32| RootMotion::between(&sample(from), &sample(to));
+
+        let (from, to) = self.step;
+        let duration = self.duration();
+        let root_motion = if self.loop_mode == LoopMode::Loop && duration > 0.0 {
+            // Sum up motion of every (maybe partial) loop
+            let loops = ((to / duration).floor() - (from / duration).floor()).abs() as usize;
+            let (from_time, to_time) = (from.rem_euclid(duration), to.rem_euclid(duration));
+            let (end, start) = if to >= from {
+                (duration, 0.0)
+            } else {
+                (0.0, duration)
+            };
+            if loops == 0 {
+                motion(from_time, to_time)
+            } else {
+                let full_loop = motion(start, end);
+                let mut root_motion = motion(from_time, end);
+                for _ in 1..loops {
+                    root_motion = root_motion.then(full_loop);
+                }
+                root_motion.then(motion(start, to_time))
+            }
+        } else {
+            motion(self.time_at(from), self.time_at(to))
+        };
+
+        // Remove horizontal motion and turning from the pose
+        let start = sample(0.0);
+        let transform = &mut model.transforms[root];
+        transform.translation = vec3(
+            start.translation.x,
+            transform.translation.y,
+            start.translation.z,
+        );
+        let turn = normalize_angle(yaw(transform.rotation) - yaw(start.rotation));
+        transform.rotation = quat_mul(yaw_quat(-turn), transform.rotation);
+
+        Ok(root_motion)
+    }
+}
+
+/// Rotation around the Y axis, that turns Z axis to where the quaternion turns it
+fn yaw(q: vec4<f32>) -> f32 {
+    let forward_x = 2.0 * (q.x * q.z + q.w * q.y);
+    let forward_z = 1.0 - 2.0 * (q.x * q.x + q.y * q.y);
+    forward_x.atan2(forward_z)
+}
+
+fn yaw_quat(yaw: f32) -> vec4<f32> {
+    vec4(0.0, (yaw / 2.0).sin(), 0.0, (yaw / 2.0).cos())
+}
+
+fn rotate_y(v: vec3<f32>, angle: f32) -> vec3<f32> {
+    let (sin, cos) = angle.sin_cos();
+    vec3(v.x * cos + v.z * sin, v.y, -v.x * sin + v.z * cos)
+}
+
+/// Wraps the angle (in radians) into -PI..PI
+fn normalize_angle(angle: f32) -> f32 {
+    use std::f32::consts::PI;
+    (angle + PI).rem_euclid(2.0 * PI) - PI
+}
@@ -0,0 +1,71 @@
+use super::*;
+
+impl Model {
+    /// All scenes of the glTF document
+    pub fn scenes(&self) -> gltf::iter::Scenes<'_> {
+        self.document.scenes()
+    }
+
+    /// Currently drawn scene, [None] if the document has no scenes
+    pub fn active_scene(&self) -> Option<gltf::Scene<'_>> {
+        self.scene
+            .and_then(|index| self.document.scenes().nth(index))
+    }
+
+    /// Finds index of a scene in the document
+    pub fn scene_index(&self, id: impl Into<Id>) -> Result<usize, SceneError> {
+        let id = id.into();
+        let index = match &id {
+            Id::Index(index) => self
+                .document
+                .scenes()
+                .nth(*index)
+                .map(|scene| scene.index()),
+            Id::Name(name) => self
+                .document
+                .scenes()
+                .find(|scene| scene.name() == Some(name))
+                .map(|scene| scene.index()),
+        };
+        index.ok_or(SceneError::NotFound(id))
+    }
+
+    /// Selects the scene to draw. Node transforms, animations and overrides are shared by all scenes
+    pub fn set_scene(&mut self, id: impl Into<Id>) -> Result<(), SceneError> {
+        self.scene = Some(self.scene_index(id)?);
+        Ok(())
+    }
+
+    /// Root nodes of the active scene. Without scenes, all nodes, that have no parent, are roots
+    pub fn root_nodes(&self) -> Vec<gltf::Node<'_>> {
+        match self.active_scene() {
+            Some(scene) => scene.nodes().collect(),
+            None => self
+                .parentless_nodes
+                .iter()
+                .filter_map(|&index| self.document.nodes().nth(index))
+                .collect(),
+        }
+    }
+}
+
+/// The default scene of the document, or the first one if there is no default
+pub(crate) fn default_scene(document: &gltf::Document) -> Option<usize> {
+    document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .map(|scene| scene.index())
+}
+
+/// Indices of all nodes, that have no parent
+pub(crate) fn parentless_nodes(document: &gltf::Document) -> Vec<usize> {
+    let children: std::collections::HashSet<usize> = document
+        .nodes()
+        .flat_map(|node| node.children().map(|child| child.index()))
+        .collect();
+    document
+        .nodes()
+        .map(|node| node.index())
+        .filter(|index| !children.contains(index))
+        .collect()
+}
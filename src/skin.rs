@@ -31,13 +31,18 @@ impl Skin {
         Ok(Self { joints })
     }
 
-    pub fn uniforms(&self, node_transforms: &[mat4<f32>]) -> impl ugli::Uniforms {
+    /// Skinning matrix of every joint, given world transforms of all nodes
+    pub fn joint_matrices(&self, node_transforms: &[mat4<f32>]) -> Vec<mat4<f32>> {
         let mut transforms = Vec::with_capacity(self.joints.len());
         for joint in self.joints.iter() {
-            transforms.push(node_transforms[joint.node_index] * joint.inverse_bind_matrix);
+            // Matrices are transposed, so this is `node * inverse_bind_matrix`
+            transforms.push(joint.inverse_bind_matrix * node_transforms[joint.node_index]);
         }
+        transforms
+    }
 
-        ugli::SingleUniform::new("u_joint_matrices[0]", transforms)
+    pub fn uniforms(&self, node_transforms: &[mat4<f32>]) -> impl ugli::Uniforms {
+        ugli::SingleUniform::new("u_joint_matrices[0]", self.joint_matrices(node_transforms))
     }
 }
 
@@ -0,0 +1,457 @@
+use super::*;
+
+/// Value of a state machine parameter
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Parameter {
+    Float(f32),
+    Bool(bool),
+    /// Bool, that is reset when a transition uses it
+    Trigger(bool),
+}
+
+/// What a state plays
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Motion {
+    Clip(Id),
+    /// Clips placed on a line, the two closest to the parameter value are blended
+    BlendSpace1d {
+        parameter: String,
+        clips: Vec<(f32, Id)>,
+    },
+    /// Clips placed on a plane, blended by inverse distance to the parameter values
+    BlendSpace2d {
+        x: String,
+        y: String,
+        clips: Vec<([f32; 2], Id)>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct State {
+    pub name: String,
+    pub motion: Motion,
+    #[serde(default = "default_speed")]
+    pub speed: f32,
+    #[serde(default = "default_looping")]
+    pub looping: bool,
+}
+
+fn default_speed() -> f32 {
+    1.0
+}
+
+fn default_looping() -> bool {
+    true
+}
+
+impl State {
+    pub fn new(name: impl Into<String>, motion: Motion) -> Self {
+        Self {
+            name: name.into(),
+            motion,
+            speed: default_speed(),
+            looping: default_looping(),
+        }
+    }
+
+    pub fn with_speed(self, speed: f32) -> Self {
+        Self { speed, ..self }
+    }
+
+    pub fn with_looping(self, looping: bool) -> Self {
+        Self { looping, ..self }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Condition {
+    Greater {
+        parameter: String,
+        value: f32,
+    },
+    Less {
+        parameter: String,
+        value: f32,
+    },
+    Bool {
+        parameter: String,
+        value: bool,
+    },
+    Trigger(String),
+    /// Current state has played for the given number of loops (e.g. 1.0 means to the end once)
+    ExitTime(f32),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transition {
+    /// State to transition from, any state if [None]
+    pub from: Option<String>,
+    pub to: String,
+    /// All conditions have to be met for the transition to happen
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    /// Crossfade duration in seconds
+    #[serde(default)]
+    pub duration: f32,
+}
+
+impl Transition {
+    pub fn new(from: Option<&str>, to: impl Into<String>) -> Self {
+        Self {
+            from: from.map(str::to_owned),
+            to: to.into(),
+            conditions: Vec::new(),
+            duration: 0.0,
+        }
+    }
+
+    pub fn with_condition(mut self, condition: Condition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    pub fn with_duration(self, duration: f32) -> Self {
+        Self { duration, ..self }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ActiveTransition {
+    from: usize,
+    from_phase: f32,
+    elapsed: f32,
+    duration: f32,
+}
+
+/// Data-driven animation state machine. Can be built from code or deserialized,
+/// for example from JSON:
+/// ```json
+/// {
+///     "parameters": { "speed": { "Float": 0.0 }, "jump": { "Trigger": false } },
+///     "states": [
+///         { "name": "Locomotion", "motion": { "BlendSpace1d": {
+///             "parameter": "speed", "clips": [[0.0, "Idle"], [1.5, "Walk"], [4.0, "Run"]]
+///         } } },
+///         { "name": "Jump", "motion": { "Clip": "Jump" }, "looping": false }
+///     ],
+///     "transitions": [
+///         { "from": "Locomotion", "to": "Jump", "conditions": [{ "Trigger": "jump" }], "duration": 0.1 },
+///         { "from": "Jump", "to": "Locomotion", "conditions": [{ "ExitTime": 1.0 }], "duration": 0.2 }
+///     ]
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AnimationStateMachine {
+    #[serde(default)]
+    pub parameters: HashMap<String, Parameter>,
+    pub states: Vec<State>,
+    #[serde(default)]
+    pub transitions: Vec<Transition>,
+    /// Name of the initial state, first state is used if [None]
+    #[serde(default)]
+    pub initial: Option<String>,
+
+    #[serde(skip)]
+    current: Option<usize>,
+    /// Time in the current state, measured in loops
+    #[serde(skip)]
+    phase: f32,
+    #[serde(skip)]
+    transition: Option<ActiveTransition>,
+}
+
+impl AnimationStateMachine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_state(mut self, state: State) -> Self {
+        self.states.push(state);
+        self
+    }
+
+    pub fn with_transition(mut self, transition: Transition) -> Self {
+        self.transitions.push(transition);
+        self
+    }
+
+    pub fn with_parameter(mut self, name: impl Into<String>, value: Parameter) -> Self {
+        self.parameters.insert(name.into(), value);
+        self
+    }
+
+    pub fn set_float(&mut self, name: &str, value: f32) {
+        self.parameters
+            .insert(name.to_owned(), Parameter::Float(value));
+    }
+
+    pub fn set_bool(&mut self, name: &str, value: bool) {
+        self.parameters
+            .insert(name.to_owned(), Parameter::Bool(value));
+    }
+
+    /// Sets the trigger, it stays set until some transition uses it
+    pub fn set_trigger(&mut self, name: &str) {
+        self.parameters
+            .insert(name.to_owned(), Parameter::Trigger(true));
+    }
+
+    fn float(&self, name: &str) -> f32 {
+        match self.parameters.get(name) {
+            Some(Parameter::Float(value)) => *value,
+            _ => 0.0,
+        }
+    }
+
+    /// Index of the current state
+    fn current(&self) -> usize {
+        self.current.unwrap_or_else(|| {
+            self.initial
+                .as_deref()
+                .and_then(|initial| self.state_index(initial))
+                .unwrap_or(0)
+        })
+    }
+
+    fn state_index(&self, name: &str) -> Option<usize> {
+        self.states.iter().position(|state| state.name == name)
+    }
+
+    /// Name of the current state
+    pub fn current_state(&self) -> Option<&str> {
+        self.states
+            .get(self.current())
+            .map(|state| state.name.as_str())
+    }
+
+    /// Returns true while crossfading between states
+    pub fn in_transition(&self) -> bool {
+        self.transition.is_some()
+    }
+
+    /// Jumps to the state immediately, without a crossfade
+    pub fn jump_to(&mut self, state: &str) {
+        match self.state_index(state) {
+            Some(index) => {
+                self.current = Some(index);
+                self.phase = 0.0;
+                self.transition = None;
+            }
+            None => log::error!("State {:?} not found", state),
+        }
+    }
+
+    /// Clips of the state and their weights for current parameter values
+    fn clip_weights(
+        &self,
+        model: &Model,
+        state: usize,
+    ) -> Result<Vec<(usize, f32)>, AnimationError> {
+        let clip = |id: &Id| model.animation_index(id.clone());
+        match &self.states[state].motion {
+            Motion::Clip(id) => Ok(vec![(clip(id)?, 1.0)]),
+            Motion::BlendSpace1d { parameter, clips } => {
+                let value = self.float(parameter);
+                let clips = clips
+                    .iter()
+                    .sorted_by(|(a, _), (b, _)| a.total_cmp(b))
+                    .collect_vec();
+                let Some(&&(first_position, ref first)) = clips.first() else {
+                    return Ok(Vec::new());
+                };
+                if value <= first_position {
+                    return Ok(vec![(clip(first)?, 1.0)]);
+                }
+                for window in clips.windows(2) {
+                    let (a, a_clip) = window[0];
+                    let (b, b_clip) = window[1];
+                    if value <= *b {
+                        let t = if b > a { (value - a) / (b - a) } else { 1.0 };
+                        return Ok(vec![(clip(a_clip)?, 1.0 - t), (clip(b_clip)?, t)]);
+                    }
+                }
+                let (_, last) = clips[clips.len() - 1];
+                Ok(vec![(clip(last)?, 1.0)])
+            }
+            Motion::BlendSpace2d { x, y, clips } => {
+                let value = vec2(self.float(x), self.float(y));
+                let mut weights = Vec::with_capacity(clips.len());
+                for ([x, y], id) in clips {
+                    let delta = vec2(*x, *y) - value;
+                    let distance_sqr = vec2::dot(delta, delta);
+                    if distance_sqr < 1e-6 {
+                        return Ok(vec![(clip(id)?, 1.0)]);
+                    }
+                    weights.push((clip(id)?, 1.0 / distance_sqr));
+                }
+                Ok(weights)
+            }
+        }
+    }
+
+    /// Duration of one loop of the state, which is weighted duration of it's clips
+    fn state_duration(&self, model: &Model, clips: &[(usize, f32)]) -> f32 {
+        let total_weight: f32 = clips.iter().map(|(_, weight)| weight).sum();
+        if total_weight <= 0.0 {
+            return 0.0;
+        }
+        clips
+            .iter()
+            .map(|&(clip, weight)| model.animations[clip].duration() * weight)
+            .sum::<f32>()
+            / total_weight
+    }
+
+    /// Samples the state at the given phase (in loops)
+    fn sample_state(
+        &self,
+        model: &Model,
+        state: usize,
+        phase: f32,
+    ) -> Result<Pose, AnimationError> {
+        let clips = self.clip_weights(model, state)?;
+        let phase = if self.states[state].looping {
+            phase.rem_euclid(1.0)
+        } else {
+            phase.clamp(0.0, 1.0)
+        };
+
+        let poses = clips
+            .iter()
+            .map(|&(clip, weight)| {
+                let animation = &model.animations[clip];
+                let mut pose = model.rest_pose();
+                animation.sample(phase * animation.duration(), &mut pose);
+                (pose, weight)
+            })
+            .collect_vec();
+        Ok(
+            Pose::weighted(poses.iter().map(|(pose, weight)| (pose, *weight)))
+                .unwrap_or_else(|| model.rest_pose()),
+        )
+    }
+
+    fn advance_phase(
+        &self,
+        model: &Model,
+        state: usize,
+        phase: f32,
+        delta_time: f32,
+    ) -> Result<f32, AnimationError> {
+        let clips = self.clip_weights(model, state)?;
+        let duration = self.state_duration(model, &clips);
+        if duration <= 0.0 {
+            return Ok(phase);
+        }
+        Ok(phase + delta_time * self.states[state].speed / duration)
+    }
+
+    fn condition_met(&self, condition: &Condition) -> bool {
+        match condition {
+            Condition::Greater { parameter, value } => self.float(parameter) > *value,
+            Condition::Less { parameter, value } => self.float(parameter) < *value,
+            Condition::Bool { parameter, value } => {
+                matches!(self.parameters.get(parameter), Some(Parameter::Bool(current)) if current == value)
+            }
+            Condition::Trigger(parameter) => {
+                matches!(
+                    self.parameters.get(parameter),
+                    Some(Parameter::Trigger(true))
+                )
+            }
+            Condition::ExitTime(time) => self.phase >= *time,
+        }
+    }
+
+    /// Starts the first transition, whose conditions are met
+    fn check_transitions(&mut self) {
+        let current = self.current();
+        let Some(transition) = self
+            .transitions
+            .iter()
+            .filter(|transition| match &transition.from {
+                Some(from) => self.state_index(from) == Some(current),
+                None => self.state_index(&transition.to) != Some(current),
+            })
+            .find(|transition| {
+                transition
+                    .conditions
+                    .iter()
+                    .all(|condition| self.condition_met(condition))
+            })
+            .cloned()
+        else {
+            return;
+        };
+
+        let Some(to) = self.state_index(&transition.to) else {
+            log::error!("State {:?} not found", transition.to);
+            return;
+        };
+
+        // Consume triggers
+        for condition in &transition.conditions {
+            if let Condition::Trigger(parameter) = condition {
+                self.parameters
+                    .insert(parameter.clone(), Parameter::Trigger(false));
+            }
+        }
+
+        self.transition = (transition.duration > 0.0).then_some(ActiveTransition {
+            from: current,
+            from_phase: self.phase,
+            elapsed: 0.0,
+            duration: transition.duration,
+        });
+        self.current = Some(to);
+        self.phase = 0.0;
+    }
+
+    /// Advances the state machine and returns the resulting pose for the model.
+    /// Animated material, camera and light properties are not part of the pose, so clips with them
+    /// are reported when their state is entered, and can be applied with [Model::apply_animation_properties]
+    pub fn update(&mut self, model: &Model, delta_time: f32) -> Result<Pose, AnimationError> {
+        if self.states.is_empty() {
+            return Ok(model.rest_pose());
+        }
+        let previous = self.current;
+        self.current = Some(self.current());
+
+        if self.transition.is_none() {
+            self.check_transitions();
+        }
+
+        let current = self.current();
+        if previous != Some(current) {
+            for (clip, _) in self.clip_weights(model, current)? {
+                if model.animations[clip].has_properties() {
+                    log::warn!(
+                        "Animated properties of clip #{} in state {:?} are not applied by the state machine",
+                        clip,
+                        self.states[current].name
+                    );
+                }
+            }
+        }
+        self.phase = self.advance_phase(model, current, self.phase, delta_time)?;
+        let mut pose = self.sample_state(model, current, self.phase)?;
+
+        if let Some(mut transition) = self.transition {
+            transition.from_phase =
+                self.advance_phase(model, transition.from, transition.from_phase, delta_time)?;
+            transition.elapsed += delta_time;
+
+            let weight = (transition.elapsed / transition.duration).clamp(0.0, 1.0);
+            if weight >= 1.0 {
+                self.transition = None;
+            } else {
+                let mut from = self.sample_state(model, transition.from, transition.from_phase)?;
+                from.blend(&pose, weight);
+                pose = from;
+                self.transition = Some(transition);
+            }
+        }
+
+        Ok(pose)
+    }
+}
@@ -0,0 +1,247 @@
+use super::*;
+
+/// Named time range of an animation, that can be played as an independent animation.
+/// Useful for assets, that pack several actions into one animation track
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SubClip {
+    pub name: String,
+    pub start: f32,
+    pub end: f32,
+}
+
+impl SubClip {
+    pub fn new(name: impl Into<String>, start: f32, end: f32) -> Self {
+        Self {
+            name: name.into(),
+            start,
+            end,
+        }
+    }
+}
+
+impl Animation {
+    /// Part of the animation between `start` and `end`, shifted to start at 0.
+    /// Keyframes are inserted at the boundaries, so the sliced animation looks exactly the same
+    pub fn slice(&self, start: f32, end: f32) -> Animation {
+        let end = end.max(start);
+        let channels = self
+            .channels
+            .iter()
+            .map(|channel| Channel {
+                target: channel.target,
+                interpolation: channel.interpolation,
+                inputs: channel.slice_inputs(start, end),
+                outputs: match &channel.outputs {
+                    Outputs::Translation(translations) => {
+                        Outputs::Translation(channel.slice_outputs(translations, start, end, lerp))
+                    }
+                    Outputs::Rotation(rotations) => {
+                        Outputs::Rotation(channel.slice_outputs(rotations, start, end, slerp))
+                    }
+                    Outputs::QuantizedRotation(rotations) => {
+                        let rotations = rotations
+                            .iter()
+                            .copied()
+                            .map(dequantize_rotation)
+                            .collect_vec();
+                        Outputs::QuantizedRotation(
+                            channel
+                                .slice_outputs(&rotations, start, end, slerp)
+                                .into_iter()
+                                .map(quantize_rotation)
+                                .collect(),
+                        )
+                    }
+                    Outputs::Scale(scales) => {
+                        Outputs::Scale(channel.slice_outputs(scales, start, end, lerp))
+                    }
+                    Outputs::Weights(outputs) => Outputs::Weights(
+                        outputs
+                            .iter()
+                            .map(|outputs| channel.slice_outputs(outputs, start, end, lerp_f32))
+                            .collect(),
+                    ),
+                    Outputs::Property { pointer, outputs } => Outputs::Property {
+                        pointer: *pointer,
+                        outputs: outputs
+                            .iter()
+                            .map(|outputs| channel.slice_outputs(outputs, start, end, lerp_f32))
+                            .collect(),
+                    },
+                },
+            })
+            .collect();
+
+        Animation {
+            name: None,
+            channels,
+            events: self
+                .events
+                .iter()
+                .filter(|event| event.time >= start && event.time <= end)
+                .map(|event| AnimationEvent {
+                    name: event.name.clone(),
+                    time: event.time - start,
+                })
+                .collect(),
+            clips: Vec::new(),
+            additive: self.additive.clone(),
+        }
+    }
+
+    /// Slice of the animation, named after the sub-clip
+    pub fn sub_clip(&self, clip: &SubClip) -> Animation {
+        Animation {
+            name: Some(clip.name.clone()),
+            ..self.slice(clip.start, clip.end)
+        }
+    }
+}
+
+impl Channel {
+    fn slice_inputs(&self, start: f32, end: f32) -> Vec<f32> {
+        let mut inputs = vec![0.0];
+        inputs.extend(
+            self.inputs
+                .iter()
+                .filter(|&&time| time > start && time < end)
+                .map(|time| time - start),
+        );
+        if end > start {
+            inputs.push(end - start);
+        }
+        inputs
+    }
+
+    fn slice_outputs<T>(
+        &self,
+        outputs: &[T],
+        start: f32,
+        end: f32,
+        lerp: impl Fn(T, T, f32) -> T,
+    ) -> Vec<T>
+    where
+        T: Copy + std::ops::Add<Output = T> + std::ops::Mul<f32, Output = T>,
+    {
+        let boundary = |time: f32, from_left: bool| {
+            let value = self.sample(time, outputs, &lerp);
+            match self.interpolation {
+                Interpolation::CubicSpline => {
+                    let tangent = self.cubic_derivative(time, outputs, from_left);
+                    vec![tangent, value, tangent]
+                }
+                _ => vec![value],
+            }
+        };
+        let values_per_key = match self.interpolation {
+            Interpolation::CubicSpline => 3,
+            _ => 1,
+        };
+
+        let mut sliced = boundary(start, false);
+        for (index, _) in self
+            .inputs
+            .iter()
+            .enumerate()
+            .filter(|&(_, &time)| time > start && time < end)
+        {
+            sliced
+                .extend_from_slice(&outputs[index * values_per_key..(index + 1) * values_per_key]);
+        }
+        if end > start {
+            sliced.extend(boundary(end, true));
+        }
+        sliced
+    }
+
+    /// Derivative of a cubic spline at `time`. At keyframes, `from_left` selects the in-tangent instead of the out-tangent
+    fn cubic_derivative<T>(&self, time: f32, outputs: &[T], from_left: bool) -> T
+    where
+        T: Copy + std::ops::Add<Output = T> + std::ops::Mul<f32, Output = T>,
+    {
+        let next = if from_left {
+            self.inputs.partition_point(|&t| t < time)
+        } else {
+            self.inputs.partition_point(|&t| t <= time)
+        };
+        if next == 0 || next == self.inputs.len() {
+            // Value is constant outside of the keyframes
+            return outputs[1] * 0.0;
+        }
+        let prev = next - 1;
+
+        let delta_time = self.inputs[next] - self.inputs[prev];
+        let t = (time - self.inputs[prev]) / delta_time;
+        let t2 = t * t;
+        let value = |index: usize| outputs[index * 3 + 1];
+        let in_tangent = |index: usize| outputs[index * 3];
+        let out_tangent = |index: usize| outputs[index * 3 + 2];
+
+        value(prev) * ((6.0 * t2 - 6.0 * t) / delta_time)
+            + out_tangent(prev) * (3.0 * t2 - 4.0 * t + 1.0)
+            + value(next) * ((6.0 * t - 6.0 * t2) / delta_time)
+            + in_tangent(next) * (3.0 * t2 - 2.0 * t)
+    }
+}
+
+impl Model {
+    /// Adds the sub-clip of an animation as a new animation, returns it's index
+    pub fn add_sub_clip(
+        &mut self,
+        id: impl Into<Id>,
+        clip: &SubClip,
+    ) -> Result<usize, AnimationError> {
+        let animation = self.animation(id)?.sub_clip(clip);
+        Ok(self.add_animation(animation))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slice() {
+        let animation = Animation {
+            events: vec![
+                AnimationEvent {
+                    name: "before".to_owned(),
+                    time: 0.25,
+                },
+                AnimationEvent {
+                    name: "inside".to_owned(),
+                    time: 1.0,
+                },
+            ],
+            ..Animation::new(vec![Channel {
+                target: 0,
+                interpolation: Interpolation::Linear,
+                inputs: vec![0.0, 1.0, 2.0, 3.0],
+                outputs: Outputs::Translation((0..4).map(|x| vec3(x as f32, 0.0, 0.0)).collect()),
+            }])
+        };
+
+        let clip = animation.sub_clip(&SubClip::new("middle", 0.5, 2.5));
+        assert_eq!(clip.name.as_deref(), Some("middle"));
+        assert_eq!(clip.duration(), 2.0);
+
+        let channel = &clip.channels[0];
+        assert_eq!(channel.inputs, vec![0.0, 0.5, 1.5, 2.0]);
+        let Outputs::Translation(translations) = &channel.outputs else {
+            panic!("Sliced channel must keep animating translation");
+        };
+        // Values at the boundaries are sampled from the original animation
+        assert_eq!(
+            translations.iter().map(|t| t.x).collect_vec(),
+            vec![0.5, 1.0, 2.0, 2.5]
+        );
+
+        assert_eq!(
+            clip.events,
+            vec![AnimationEvent {
+                name: "inside".to_owned(),
+                time: 0.5,
+            }]
+        );
+    }
+}
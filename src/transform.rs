@@ -0,0 +1,265 @@
+use super::*;
+
+/// Local transform of a node, decomposed into translation, rotation and scale
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: vec3<f32>,
+    /// Rotation quaternion, stored as (x, y, z, w) like in glTF
+    pub rotation: vec4<f32>,
+    pub scale: vec3<f32>,
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Self {
+            translation: vec3::ZERO,
+            rotation: vec4(0.0, 0.0, 0.0, 1.0),
+            scale: vec3(1.0, 1.0, 1.0),
+        }
+    }
+
+    /// Matrix of this transform (T * R * S), in the same layout as all other node matrices of a [Model].
+    /// Those are transposed (see how shaders multiply by them), so the parent matrix goes on the right:
+    /// `child.matrix() * parent_matrix`
+    pub fn matrix(&self) -> mat4<f32> {
+        let Self {
+            translation,
+            rotation,
+            scale,
+        } = *self;
+        mat4::new(
+            gltf::scene::Transform::Decomposed {
+                translation: [translation.x, translation.y, translation.z],
+                rotation: [rotation.x, rotation.y, rotation.z, rotation.w],
+                scale: [scale.x, scale.y, scale.z],
+            }
+            .matrix(),
+        )
+    }
+
+    /// Interpolates between two transforms, rotations are slerped
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            translation: self.translation + (other.translation - self.translation) * t,
+            rotation: slerp(self.rotation, other.rotation, t),
+            scale: self.scale + (other.scale - self.scale) * t,
+        }
+    }
+
+    /// Applies the difference between `additive` and `reference` on top of this transform, scaled by `weight`.
+    /// Translation difference is added, rotation and scale differences are multiplied
+    pub fn add(&self, additive: &Self, reference: &Self, weight: f32) -> Self {
+        let delta_rotation = quat_mul(quat_conjugate(reference.rotation), additive.rotation);
+        let delta_scale = |additive: f32, reference: f32| {
+            let delta = if reference != 0.0 {
+                additive / reference
+            } else {
+                1.0
+            };
+            1.0 + (delta - 1.0) * weight
+        };
+        Self {
+            translation: self.translation + (additive.translation - reference.translation) * weight,
+            rotation: normalize_quat(quat_mul(
+                self.rotation,
+                slerp(vec4(0.0, 0.0, 0.0, 1.0), delta_rotation, weight),
+            )),
+            scale: vec3(
+                self.scale.x * delta_scale(additive.scale.x, reference.scale.x),
+                self.scale.y * delta_scale(additive.scale.y, reference.scale.y),
+                self.scale.z * delta_scale(additive.scale.z, reference.scale.z),
+            ),
+        }
+    }
+
+    /// Transform of a child node with `local` transform, when this is the transform of it's parent.
+    /// Shear (from non-uniformly scaled rotated parents) can't be represented, so it is ignored
+    pub fn compose(&self, local: &Self) -> Self {
+        Self {
+            translation: self.transform_point(local.translation),
+            rotation: normalize_quat(quat_mul(self.rotation, local.rotation)),
+            scale: vec3(
+                self.scale.x * local.scale.x,
+                self.scale.y * local.scale.y,
+                self.scale.z * local.scale.z,
+            ),
+        }
+    }
+
+    /// Applies this transform (scale, then rotation, then translation) to a point
+    pub fn transform_point(&self, point: vec3<f32>) -> vec3<f32> {
+        let scaled = vec3(
+            point.x * self.scale.x,
+            point.y * self.scale.y,
+            point.z * self.scale.z,
+        );
+        self.translation + quat_rotate(self.rotation, scaled)
+    }
+
+    /// Weighted average of transforms. Rotations are aligned to the first one, so that
+    /// quaternions take the shortest path, and normalized. Weights don't need to sum up to 1
+    pub fn blend(transforms: impl IntoIterator<Item = (Self, f32)>) -> Self {
+        let mut transforms = transforms.into_iter().peekable();
+        let Some(&(first, _)) = transforms.peek() else {
+            return Self::identity();
+        };
+
+        let mut total_weight = 0.0;
+        let mut translation = vec3::ZERO;
+        let mut rotation = vec4(0.0, 0.0, 0.0, 0.0);
+        let mut scale = vec3::ZERO;
+        for (transform, weight) in transforms {
+            total_weight += weight;
+            translation = translation + transform.translation * weight;
+            scale = scale + transform.scale * weight;
+            let sign = if vec4::dot(first.rotation, transform.rotation) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            rotation = rotation + transform.rotation * (sign * weight);
+        }
+
+        if total_weight <= 0.0 {
+            return first;
+        }
+        Self {
+            translation: translation / total_weight,
+            rotation: normalize_quat(rotation),
+            scale: scale / total_weight,
+        }
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl From<gltf::scene::Transform> for Transform {
+    fn from(value: gltf::scene::Transform) -> Self {
+        let ([tx, ty, tz], [rx, ry, rz, rw], [sx, sy, sz]) = value.decomposed();
+        Self {
+            translation: vec3(tx, ty, tz),
+            rotation: vec4(rx, ry, rz, rw),
+            scale: vec3(sx, sy, sz),
+        }
+    }
+}
+
+// * ------------------------------------ Quaternions ----------------------------------- * //
+pub(crate) fn normalize_quat(q: vec4<f32>) -> vec4<f32> {
+    q * (1.0 / vec4::dot(q, q).sqrt())
+}
+
+pub(crate) fn quat_mul(a: vec4<f32>, b: vec4<f32>) -> vec4<f32> {
+    vec4(
+        a.w * b.x + a.x * b.w + a.y * b.z - a.z * b.y,
+        a.w * b.y - a.x * b.z + a.y * b.w + a.z * b.x,
+        a.w * b.z + a.x * b.y - a.y * b.x + a.z * b.w,
+        a.w * b.w - a.x * b.x - a.y * b.y - a.z * b.z,
+    )
+}
+
+/// Inverse of a unit quaternion
+pub(crate) fn quat_conjugate(q: vec4<f32>) -> vec4<f32> {
+    vec4(-q.x, -q.y, -q.z, q.w)
+}
+
+/// Rotates a vector by a unit quaternion
+pub(crate) fn quat_rotate(q: vec4<f32>, v: vec3<f32>) -> vec3<f32> {
+    let axis = vec3(q.x, q.y, q.z);
+    let t = vec3::cross(axis, v) * 2.0;
+    v + t * q.w + vec3::cross(axis, t)
+}
+
+/// Rotation around a normalized axis, angle is in radians
+pub(crate) fn quat_from_axis_angle(axis: vec3<f32>, angle: f32) -> vec4<f32> {
+    let (sin, cos) = (angle / 2.0).sin_cos();
+    vec4(axis.x * sin, axis.y * sin, axis.z * sin, cos)
+}
+
+/// Shortest rotation, that turns direction `from` to direction `to`
+pub(crate) fn quat_between(from: vec3<f32>, to: vec3<f32>) -> vec4<f32> {
+    let from = from * (1.0 / from.len());
+    let to = to * (1.0 / to.len());
+    let dot = vec3::dot(from, to);
+    if dot < -0.9999 {
+        // Opposite directions, rotate by 180 degrees around any perpendicular axis
+        let axis = if from.x.abs() < 0.9 {
+            vec3::cross(from, vec3(1.0, 0.0, 0.0))
+        } else {
+            vec3::cross(from, vec3(0.0, 1.0, 0.0))
+        };
+        let axis = axis * (1.0 / axis.len());
+        return vec4(axis.x, axis.y, axis.z, 0.0);
+    }
+    let axis = vec3::cross(from, to);
+    normalize_quat(vec4(axis.x, axis.y, axis.z, 1.0 + dot))
+}
+
+/// Spherical linear interpolation between two rotation quaternions, taking the shortest path
+pub(crate) fn slerp(a: vec4<f32>, b: vec4<f32>, t: f32) -> vec4<f32> {
+    let dot = vec4::dot(a, b);
+    let (b, dot) = if dot < 0.0 {
+        (b * -1.0, -dot)
+    } else {
+        (b, dot)
+    };
+
+    // Quaternions are almost the same, nlerp is precise enough and doesn't divide by zero
+    if dot > 0.9995 {
+        return normalize_quat(a + (b - a) * t);
+    }
+
+    let theta = dot.acos();
+    let sin_theta = theta.sin();
+    a * (((1.0 - t) * theta).sin() / sin_theta) + b * ((t * theta).sin() / sin_theta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rotation around the z axis
+    fn rotation_z(angle: f32) -> vec4<f32> {
+        vec4(0.0, 0.0, (angle / 2.0).sin(), (angle / 2.0).cos())
+    }
+
+    fn assert_same_rotation(a: vec4<f32>, b: vec4<f32>) {
+        assert!(vec4::dot(a, b).abs() > 1.0 - 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn slerp_rotations() {
+        let identity = vec4(0.0, 0.0, 0.0, 1.0);
+        let quarter = rotation_z(std::f32::consts::FRAC_PI_2);
+
+        assert_same_rotation(slerp(identity, quarter, 0.0), identity);
+        assert_same_rotation(slerp(identity, quarter, 1.0), quarter);
+        assert_same_rotation(
+            slerp(identity, quarter, 0.5),
+            rotation_z(std::f32::consts::FRAC_PI_4),
+        );
+        // Negated quaternion is the same rotation, so the shortest path is taken
+        assert_same_rotation(
+            slerp(identity, quarter * -1.0, 0.5),
+            rotation_z(std::f32::consts::FRAC_PI_4),
+        );
+    }
+
+    #[test]
+    fn lerp_transforms() {
+        let a = Transform::identity();
+        let b = Transform {
+            translation: vec3(2.0, 0.0, 0.0),
+            rotation: rotation_z(std::f32::consts::PI),
+            scale: vec3(3.0, 3.0, 3.0),
+        };
+        let half = a.lerp(&b, 0.5);
+        assert_eq!(half.translation, vec3(1.0, 0.0, 0.0));
+        assert_eq!(half.scale, vec3(2.0, 2.0, 2.0));
+        assert_same_rotation(half.rotation, rotation_z(std::f32::consts::FRAC_PI_2));
+    }
+}